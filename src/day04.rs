@@ -1,162 +1,92 @@
 use aoc_runner_derive::aoc;
 
+use crate::utils::Grid;
+
+mod search;
+use search::{Direction, Template, Transform};
+
 #[aoc(day4, part1)]
 fn part1(input: &str) -> u32 {
     let soup = Soup::from(input);
-
-    let dirs = [
-        (1, 0),
-        (1, 1),
-        (0, 1),
-        (-1, 1),
-        (-1, 0),
-        (-1, -1),
-        (0, -1),
-        (1, -1),
-    ];
-
-    let target = "xmas";
-    let mut res = 0;
-
-    for y in 0..soup.height() as i32 {
-        for x in 0..soup.width() as i32 {
-            let words = dirs.iter().filter_map(|&step| {
-                let word: String = soup.word_iter((x, y), step, target.len()).collect();
-                if word == target {
-                    Some(word)
-                } else {
-                    None
-                }
-            });
-
-            res += words.count() as u32
-        }
-    }
-
-    res
+    soup.find_words(&["xmas"], &Direction::ALL).len() as u32
 }
 
 #[aoc(day4, part2)]
 fn part2(input: &str) -> u32 {
-    let target = "mas";
-    let offsets = [(-1, -1), (1, 1), (-1, 1), (1, -1)]
-        .into_iter()
-        .map(|(a, b)| {
-            let half = (target.len() as i32 - 1) / 2;
-            (a * half, b * half)
-        })
-        .collect::<Vec<(i32, i32)>>();
-
     let soup = Soup::from(input);
-
-    let mut res = 0;
-
-    for y in 0..soup.height() as i32 {
-        for x in 0..soup.width() as i32 {
-            let words = offsets.iter().filter_map(|&offset| {
-                let step = (offset.0 * -1, offset.1 * -1);
-                let start = (x + offset.0, y + offset.1);
-                let word: String = soup.word_iter(start, step, target.len()).collect();
-
-                if word == target {
-                    Some(word)
-                } else {
-                    None
-                }
-            });
-
-            if words.count() >= 2 {
-                res += 1;
-            }
-        }
-    }
-
-    res
+    let cross = Template::try_from(
+        r"m.s
+.a.
+m.s",
+    )
+    .unwrap();
+
+    soup.find_template(&cross, &Transform::ALL).len() as u32
 }
 
 #[derive(Debug, Clone, PartialEq)]
 struct Soup {
-    cells: Vec<char>,
-    width: usize,
+    grid: Grid<Option<char>>,
+    case_sensitive: bool,
 }
 
 impl Soup {
-    fn get(&self, (x, y): (i32, i32)) -> Option<char> {
-        if x < 0 || x >= self.width() as i32 || y < 0 || y >= self.height() as i32 {
-            return None;
-        }
+    fn new(input: &str, case_sensitive: bool) -> Self {
+        // short lines are padded with gaps, and so is any whitespace, so no
+        // word can run across them
+        let lines: Vec<Vec<char>> = input
+            .lines()
+            .map(|line| line.trim_end_matches('\r').chars().collect())
+            .collect();
+        let width = lines.iter().map(|line| line.len()).max().unwrap_or(0);
 
-        let idx = y * self.width() as i32 + x;
-        self.cells.get(idx as usize).copied()
-    }
+        let cells = lines
+            .iter()
+            .flat_map(|line| {
+                (0..width).map(|x| line.get(x).copied().filter(|c| !c.is_whitespace()))
+            })
+            .collect();
 
-    fn width(&self) -> usize {
-        self.width
+        Self {
+            grid: Grid::new(cells, width),
+            case_sensitive,
+        }
     }
 
-    fn height(&self) -> usize {
-        self.cells.len() / self.width
+    fn get(&self, position: (i32, i32)) -> Option<char> {
+        self.grid.get_xy(position).flatten()
     }
 
-    fn word_iter(
-        &self,
-        start: (i32, i32),
-        step: (i32, i32),
-        target_len: usize,
-    ) -> impl Iterator<Item = char> + use<'_> {
-        SoupIterator::new(&self, start, step, target_len)
+    fn width(&self) -> i32 {
+        self.grid.width()
     }
-}
-
-impl From<&str> for Soup {
-    fn from(value: &str) -> Self {
-        let cells = value
-            .to_lowercase()
-            .chars()
-            .filter(|x| !x.is_whitespace())
-            .collect();
-        let width = value.len() / value.lines().count();
 
-        Self { cells, width }
+    fn height(&self) -> i32 {
+        if self.grid.width() == 0 {
+            0
+        } else {
+            self.grid.height()
+        }
     }
-}
 
-struct SoupIterator<'a> {
-    soup: &'a Soup,
-    step: (i32, i32),
-    current: (i32, i32),
-    target_len: usize,
-    count: usize,
-}
+    fn fold(&self, c: char) -> char {
+        // only foldings to a single char are applied, so positions in the
+        // soup still line up with positions in the words
+        if self.case_sensitive {
+            return c;
+        }
 
-impl<'a> SoupIterator<'a> {
-    fn new(soup: &'a Soup, start: (i32, i32), step: (i32, i32), target_len: usize) -> Self {
-        Self {
-            soup,
-            current: start,
-            step,
-            target_len,
-            count: 0,
+        let mut lower = c.to_lowercase();
+        match (lower.next(), lower.next()) {
+            (Some(x), None) => x,
+            _ => c,
         }
     }
 }
 
-impl<'a> Iterator for SoupIterator<'a> {
-    type Item = char;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.count >= self.target_len {
-            return None;
-        }
-
-        if let Some(x) = self.soup.get(self.current) {
-            self.current = (self.current.0 + self.step.0, self.current.1 + self.step.1);
-            self.count += 1;
-
-            Some(x)
-        } else {
-            None
-        }
+impl From<&str> for Soup {
+    fn from(value: &str) -> Self {
+        Self::new(value, false)
     }
 }
 
@@ -181,8 +111,18 @@ MXMXAXMASX";
         assert_eq!(
             Soup::from(input),
             Soup {
-                cells: vec!['a', 'b', 'c', 'd', 'e', 'f'],
-                width: 3,
+                grid: Grid::new(
+                    vec![
+                        Some('A'),
+                        Some('B'),
+                        Some('C'),
+                        Some('D'),
+                        Some('E'),
+                        Some('F')
+                    ],
+                    3
+                ),
+                case_sensitive: false,
             }
         );
     }
@@ -196,4 +136,30 @@ MXMXAXMASX";
     fn part2_example() {
         assert_eq!(part2(INPUT), 9);
     }
+
+    #[test]
+    fn soup_from_uneven_input() {
+        let soup = Soup::from("ABC\r\nD F G\r\nH");
+
+        assert_eq!((soup.width(), soup.height()), (5, 3));
+        assert_eq!(soup.get((0, 0)), Some('A'));
+        assert_eq!(soup.get((1, 1)), None);
+        assert_eq!(soup.get((4, 1)), Some('G'));
+        assert_eq!(soup.get((3, 0)), None);
+        assert_eq!(soup.get((1, 2)), None);
+
+        let soup = Soup::from("");
+        assert_eq!((soup.width(), soup.height()), (0, 0));
+    }
+
+    #[test]
+    fn fold_letters() {
+        let soup = Soup::from("");
+        assert_eq!(soup.fold('X'), 'x');
+        assert_eq!(soup.fold('Ñ'), 'ñ');
+        assert_eq!(soup.fold('İ'), 'İ');
+
+        let soup = Soup::new("", true);
+        assert_eq!(soup.fold('X'), 'X');
+    }
 }
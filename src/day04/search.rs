@@ -0,0 +1,442 @@
+use aho_corasick::AhoCorasick;
+
+use super::Soup;
+
+type Point = (i32, i32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Direction {
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+    North,
+    NorthEast,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 8] = [
+        Self::East,
+        Self::SouthEast,
+        Self::South,
+        Self::SouthWest,
+        Self::West,
+        Self::NorthWest,
+        Self::North,
+        Self::NorthEast,
+    ];
+
+    fn step(&self) -> Point {
+        match self {
+            Self::East => (1, 0),
+            Self::SouthEast => (1, 1),
+            Self::South => (0, 1),
+            Self::SouthWest => (-1, 1),
+            Self::West => (-1, 0),
+            Self::NorthWest => (-1, -1),
+            Self::North => (0, -1),
+            Self::NorthEast => (1, -1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Transform {
+    quarter_turns: u8,
+    flipped: bool,
+}
+
+#[allow(dead_code)]
+impl Transform {
+    pub const ALL: [Transform; 8] = [
+        Self::new(0, false),
+        Self::new(1, false),
+        Self::new(2, false),
+        Self::new(3, false),
+        Self::new(0, true),
+        Self::new(1, true),
+        Self::new(2, true),
+        Self::new(3, true),
+    ];
+
+    pub const IDENTITY: Transform = Self::new(0, false);
+
+    pub const fn new(quarter_turns: u8, flipped: bool) -> Self {
+        Self {
+            quarter_turns: quarter_turns % 4,
+            flipped,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    cells: Vec<Option<char>>,
+    width: usize,
+}
+
+impl Template {
+    fn height(&self) -> usize {
+        self.cells.len() / self.width
+    }
+
+    fn transformed(&self, transform: Transform) -> Self {
+        // mirror left to right first, then rotate clockwise
+        let (width, height) = (self.width, self.height());
+        let mut cells: Vec<Option<char>> = (0..self.cells.len())
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                let x = if transform.flipped { width - 1 - x } else { x };
+                self.cells[y * width + x]
+            })
+            .collect();
+
+        let (mut width, mut height) = (width, height);
+        for _ in 0..transform.quarter_turns {
+            cells = (0..cells.len())
+                .map(|i| {
+                    let (x, y) = (i % height, i / height);
+                    cells[(height - 1 - x) * width + y]
+                })
+                .collect();
+            (width, height) = (height, width);
+        }
+
+        Self { cells, width }
+    }
+
+    fn matches_at(&self, soup: &Soup, (x, y): Point) -> bool {
+        self.cells.iter().enumerate().all(|(i, cell)| {
+            let position = (x + (i % self.width) as i32, y + (i / self.width) as i32);
+            match (cell, soup.get(position)) {
+                (_, None) => false,
+                (None, Some(_)) => true,
+                (Some(c), Some(letter)) => soup.fold(*c) == soup.fold(letter),
+            }
+        })
+    }
+}
+
+impl TryFrom<&str> for Template {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        // a '.' matches any letter; blank lines around the template are
+        // ignored, so it can start on the line after an opening quote
+        let rows: Vec<Vec<char>> = value
+            .lines()
+            .map(|line| line.chars().filter(|x| !x.is_whitespace()).collect())
+            .skip_while(|row: &Vec<char>| row.is_empty())
+            .collect();
+        let height = rows.len() - rows.iter().rev().take_while(|row| row.is_empty()).count();
+        let rows = &rows[..height];
+
+        let width = rows.first().map_or(0, |row| row.len());
+        if width == 0 {
+            return Err("Empty template".to_string());
+        }
+        if let Some(y) = rows.iter().position(|row| row.len() != width) {
+            return Err(format!(
+                "Template row {} has {} cells, expected {}",
+                y,
+                rows[y].len(),
+                width
+            ));
+        }
+
+        let cells = rows
+            .iter()
+            .flatten()
+            .map(|&x| if x == '.' { None } else { Some(x) })
+            .collect();
+
+        Ok(Self { cells, width })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WordMatch {
+    word: usize,
+    start: Point,
+    direction: Direction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TemplateMatch {
+    position: Point,
+    transform: Transform,
+}
+
+impl Soup {
+    pub fn find_words(&self, words: &[&str], directions: &[Direction]) -> Vec<WordMatch> {
+        // empty words would match between letters, where there's no start
+        let (indices, words): (Vec<usize>, Vec<String>) = words
+            .iter()
+            .enumerate()
+            .filter(|(_, word)| !word.is_empty())
+            .map(|(i, word)| (i, word.chars().map(|c| self.fold(c)).collect()))
+            .unzip();
+        if words.is_empty() {
+            return vec![];
+        }
+
+        // every word is looked for at once along each line of the soup, so
+        // the cost barely grows with the size of the dictionary
+        let automaton = AhoCorasick::new(&words).unwrap();
+        let mut matches = vec![];
+
+        for &direction in directions {
+            for (line, positions) in self.lines(direction) {
+                let offsets: Vec<usize> = line.char_indices().map(|(i, _)| i).collect();
+                for found in automaton.find_overlapping_iter(&line) {
+                    let idx = offsets.binary_search(&found.start()).unwrap();
+                    matches.push(WordMatch {
+                        word: indices[found.pattern().as_usize()],
+                        start: positions[idx],
+                        direction,
+                    });
+                }
+            }
+        }
+
+        matches.sort_by_key(|m| (m.start.1, m.start.0, m.direction, m.word));
+        matches
+    }
+
+    pub fn find_template(
+        &self,
+        template: &Template,
+        transforms: &[Transform],
+    ) -> Vec<TemplateMatch> {
+        // symmetric templates look the same under several transforms, and
+        // each distinct shape should only be counted once
+        let mut variants: Vec<(Template, Transform)> = vec![];
+        for &transform in transforms {
+            let variant = template.transformed(transform);
+            if !variants.iter().any(|(x, _)| *x == variant) {
+                variants.push((variant, transform));
+            }
+        }
+
+        let mut matches = vec![];
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                for (variant, transform) in variants.iter() {
+                    if variant.matches_at(self, (x, y)) {
+                        matches.push(TemplateMatch {
+                            position: (x, y),
+                            transform: *transform,
+                        });
+                    }
+                }
+            }
+        }
+
+        matches
+    }
+
+    fn lines(&self, direction: Direction) -> Vec<(String, Vec<Point>)> {
+        // a line starts at every letter whose previous cell is off the soup
+        // or a gap
+        let (dx, dy) = direction.step();
+        let mut lines = vec![];
+
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                if self.get((x, y)).is_none() || self.get((x - dx, y - dy)).is_some() {
+                    continue;
+                }
+
+                let mut line = String::new();
+                let mut positions = vec![];
+                let mut current = (x, y);
+                while let Some(c) = self.get(current) {
+                    line.push(self.fold(c));
+                    positions.push(current);
+                    current = (current.0 + dx, current.1 + dy);
+                }
+                lines.push((line, positions));
+            }
+        }
+
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_template(value: &str) -> Template {
+        Template::try_from(value).unwrap()
+    }
+
+    #[test]
+    fn find_several_words() {
+        let soup = Soup::from(
+            r"CAT.
+O..D
+W..O
+DOGG",
+        );
+        let matches = soup.find_words(&["cat", "dog", "cow", "god"], &Direction::ALL);
+
+        let found: Vec<(usize, Point, Direction)> = matches
+            .iter()
+            .map(|m| (m.word, m.start, m.direction))
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                (0, (0, 0), Direction::East),
+                (2, (0, 0), Direction::South),
+                (1, (3, 1), Direction::South),
+                (1, (0, 3), Direction::East),
+                (3, (2, 3), Direction::West),
+                (3, (3, 3), Direction::North),
+            ]
+        );
+    }
+
+    #[test]
+    fn find_words_in_some_directions() {
+        let soup = Soup::from(
+            r"ABA
+BAB
+ABA",
+        );
+
+        assert_eq!(soup.find_words(&["aba"], &[Direction::East]).len(), 2);
+        assert_eq!(soup.find_words(&["aba"], &Direction::ALL).len(), 8);
+        assert_eq!(soup.find_words(&[], &Direction::ALL), vec![]);
+        assert_eq!(soup.find_words(&[""], &Direction::ALL), vec![]);
+
+        let matches = soup.find_words(&["", "bab"], &[Direction::East]);
+        assert_eq!(
+            matches,
+            vec![WordMatch {
+                word: 1,
+                start: (0, 1),
+                direction: Direction::East
+            }]
+        );
+    }
+
+    #[test]
+    fn transform_template() {
+        let template = parse_template("ab.\ncde");
+
+        assert_eq!(
+            template.transformed(Transform::new(1, false)),
+            parse_template("ca\ndb\ne.")
+        );
+        assert_eq!(
+            template.transformed(Transform::new(2, false)),
+            parse_template("edc\n.ba")
+        );
+        assert_eq!(
+            template.transformed(Transform::new(0, true)),
+            parse_template(".ba\nedc")
+        );
+        assert_eq!(
+            template.transformed(Transform::new(1, true)),
+            parse_template("e.\ndb\nca")
+        );
+    }
+
+    #[test]
+    fn find_template_with_wildcards() {
+        let soup = Soup::from(
+            r"MXS
+XAX
+MXS",
+        );
+        let template = parse_template("m.s\n.a.\nm.s");
+
+        assert_eq!(
+            soup.find_template(&template, &Transform::ALL),
+            vec![TemplateMatch {
+                position: (0, 0),
+                transform: Transform::IDENTITY
+            }]
+        );
+        assert_eq!(
+            soup.find_template(&template, &[Transform::new(1, false)]),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn find_words_with_case_and_unicode() {
+        let input = r"Ñandú
+año.Ü
+AÑOÑAN";
+        let soup = Soup::from(input);
+        assert_eq!(
+            soup.find_words(&["ÑAN", "AÑO"], &[Direction::East]).len(),
+            4
+        );
+        assert_eq!(soup.find_words(&["ñaa"], &[Direction::South]).len(), 1);
+
+        let soup = Soup::new(input, true);
+        let matches = soup.find_words(&["ÑAN", "AÑO", "año"], &[Direction::East]);
+        let found: Vec<(usize, Point)> = matches.iter().map(|m| (m.word, m.start)).collect();
+        assert_eq!(found, vec![(2, (0, 1)), (1, (0, 2)), (0, (3, 2))]);
+    }
+
+    #[test]
+    fn words_stop_at_gaps() {
+        let soup = Soup::from("AB CD\nABCD");
+
+        assert_eq!(soup.find_words(&["bc"], &[Direction::East]).len(), 1);
+        assert_eq!(soup.find_words(&["a"], &Direction::ALL).len(), 16);
+    }
+
+    #[test]
+    fn wildcards_need_a_letter() {
+        let soup = Soup::from("AB\nC");
+        let template = parse_template("a.\n..");
+
+        assert_eq!(
+            soup.find_template(&template, &[Transform::IDENTITY]),
+            vec![]
+        );
+        assert_eq!(
+            soup.find_template(&parse_template("a."), &[Transform::IDENTITY])
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn template_shape() {
+        let cross = parse_template("m.s\n.a.\nm.s");
+        assert_eq!(parse_template("\nm.s\n.a.\nm.s\n\n"), cross);
+        assert_eq!(
+            parse_template(
+                r"
+m.s
+.a.
+m.s
+"
+            ),
+            cross
+        );
+
+        assert_eq!(
+            Template::try_from("m.s\n.a\nm.s"),
+            Err("Template row 1 has 2 cells, expected 3".to_string())
+        );
+        assert_eq!(
+            Template::try_from("m.s\n\nm.s"),
+            Err("Template row 1 has 0 cells, expected 3".to_string())
+        );
+        assert_eq!(
+            Template::try_from("\n \n"),
+            Err("Empty template".to_string())
+        );
+    }
+}
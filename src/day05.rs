@@ -1,5 +1,7 @@
 use aoc_runner_derive::aoc;
-use std::cmp::Ordering;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fmt;
 
 type Rule = (u32, u32);
 type Update = Vec<u32>;
@@ -26,48 +28,351 @@ fn parse_part1(input: &str) -> (Vec<Rule>, Vec<Update>) {
     (rules, updates)
 }
 
+#[derive(Debug, Clone, PartialEq)]
+struct Cycle {
+    pages: Vec<u32>,
+}
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pages = self
+            .pages
+            .iter()
+            .chain(self.pages.first())
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>();
+        write!(f, "Contradictory rules: {}", pages.join(" -> "))
+    }
+}
+
+impl From<Cycle> for String {
+    fn from(value: Cycle) -> Self {
+        value.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Violation {
+    rule: Rule,
+    positions: (usize, usize),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (before, after) = self.rule;
+        write!(
+            f,
+            "page {} (at {}) appears after {} (at {}) but rule {}|{} requires before",
+            before, self.positions.0, after, self.positions.1, before, after
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+struct Move {
+    page: u32,
+    from: usize,
+    to: usize,
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "move {} from {} to {}", self.page, self.from, self.to)
+    }
+}
+
 struct Ruleset {
-    rules: Vec<Rule>,
+    after: HashMap<u32, Vec<u32>>,
 }
 
 impl Ruleset {
     fn new(rules: Vec<Rule>) -> Self {
-        Self { rules }
+        let mut after: HashMap<u32, Vec<u32>> = HashMap::new();
+        for (before, page) in rules {
+            let pages = after.entry(before).or_default();
+            if !pages.contains(&page) {
+                pages.push(page);
+            }
+        }
+
+        Self { after }
     }
 
     fn valid_update(&self, update: &Update) -> bool {
-        let mut sorted = update.clone();
-        sorted.sort_by(self.sort_fn());
-        sorted == *update
+        self.violations(update).is_empty()
     }
 
-    fn sort_update(&self, update: &Update) -> Option<Update> {
-        let mut res = update.clone();
-        res.sort_by(self.sort_fn());
-        Some(res)
+    fn violations(&self, update: &Update) -> Vec<Violation> {
+        let positions: HashMap<u32, usize> =
+            update.iter().enumerate().map(|(i, &x)| (x, i)).collect();
+        let positions = &positions;
+
+        let mut violations: Vec<Violation> = update
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &page)| {
+                self.pages_after(page).iter().filter_map(move |x| {
+                    let j = *positions.get(x)?;
+                    (j < i).then_some(Violation {
+                        rule: (page, *x),
+                        positions: (i, j),
+                    })
+                })
+            })
+            .collect();
+        violations.sort_by_key(|v| (v.positions.1, v.positions.0));
+
+        violations
     }
 
-    fn sort_fn<'a>(&self) -> impl Fn(&u32, &u32) -> Ordering + use<'_> {
-        |a, b| {
-            let binding = self.rules_for(*a);
-            let rule = binding.iter().find(|(_, y)| y == b);
-            if a == b {
-                Ordering::Equal
-            } else if rule.is_some() {
-                Ordering::Less
-            } else {
-                Ordering::Greater
+    fn sort_update(&self, update: &Update) -> Result<Update, Cycle> {
+        // Kahn's algorithm over the rules between the update's pages; ties
+        // keep their original order, so the result is deterministic
+        let pages: HashSet<u32> = update.iter().copied().collect();
+        let mut in_degree: HashMap<u32, usize> = update.iter().map(|&x| (x, 0)).collect();
+        for &page in pages.iter() {
+            for next in self.pages_after(page) {
+                if let Some(degree) = in_degree.get_mut(next) {
+                    *degree += 1;
+                }
+            }
+        }
+
+        let mut ready: BinaryHeap<Reverse<(usize, u32)>> = update
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| in_degree[x] == 0)
+            .map(|(i, &x)| Reverse((i, x)))
+            .collect();
+        let positions: HashMap<u32, usize> =
+            update.iter().enumerate().map(|(i, &x)| (x, i)).collect();
+
+        let mut sorted = Vec::with_capacity(update.len());
+        while let Some(Reverse((_, page))) = ready.pop() {
+            sorted.push(page);
+            for next in self.pages_after(page) {
+                if let Some(degree) = in_degree.get_mut(next) {
+                    *degree -= 1;
+                    if *degree == 0 {
+                        ready.push(Reverse((positions[next], *next)));
+                    }
+                }
             }
         }
+
+        if sorted.len() < pages.len() {
+            let left: HashSet<u32> = pages.into_iter().filter(|x| !sorted.contains(x)).collect();
+            return Err(self.cycle_within(&left));
+        }
+
+        Ok(sorted)
     }
 
+    #[allow(dead_code)]
+    fn find_cycle(&self) -> Option<Cycle> {
+        // peel off pages with no pending rules before them; whatever is left
+        // can't be ordered and contains at least one cycle
+        let mut pages: HashSet<u32> = self.after.keys().copied().collect();
+        pages.extend(self.after.values().flatten());
+
+        loop {
+            let blocked: HashSet<u32> = pages
+                .iter()
+                .flat_map(|&x| self.pages_after(x))
+                .filter(|x| pages.contains(x))
+                .copied()
+                .collect();
+            if blocked.len() == pages.len() {
+                break;
+            }
+            pages = blocked;
+        }
+
+        (!pages.is_empty()).then(|| self.cycle_within(&pages))
+    }
+
+    fn cycle_within(&self, pages: &HashSet<u32>) -> Cycle {
+        // every page in `pages` has a predecessor in it too, so walking back
+        // from any of them must eventually come round to a page seen before
+        let before = |page: u32| {
+            pages
+                .iter()
+                .copied()
+                .filter(|&x| self.pages_after(x).contains(&page))
+                .min()
+                .unwrap()
+        };
+
+        let mut path = vec![*pages.iter().min().unwrap()];
+        loop {
+            let previous = before(*path.last().unwrap());
+            if let Some(start) = path.iter().position(|&x| x == previous) {
+                // start from the lowest page, so the same cycle always
+                // reads the same way
+                let mut cycle = path[start..].to_vec();
+                cycle.reverse();
+                let lowest = (0..cycle.len()).min_by_key(|&i| cycle[i]).unwrap();
+                cycle.rotate_left(lowest);
+                return Cycle { pages: cycle };
+            }
+            path.push(previous);
+        }
+    }
+
+    fn pages_after(&self, page: u32) -> &[u32] {
+        self.after.get(&page).map_or(&[], |x| x.as_slice())
+    }
+
+    #[allow(dead_code)]
     fn rules_for(&self, page: u32) -> Vec<Rule> {
-        self.rules
+        self.pages_after(page).iter().map(|&x| (page, x)).collect()
+    }
+}
+
+#[allow(dead_code)]
+impl Ruleset {
+    fn fix(&self, update: &Update) -> Result<Vec<Move>, Cycle> {
+        // a set of pages can stay put as long as none of them has to come
+        // before an earlier one, even through pages in between, so the most
+        // that can be kept is the largest such set
+        self.sort_update(update)?;
+        let pages: HashSet<u32> = update.iter().copied().collect();
+        let reachable: Vec<HashSet<u32>> = update
+            .iter()
+            .map(|&page| {
+                let mut seen = HashSet::new();
+                let mut stack = vec![page];
+                while let Some(x) = stack.pop() {
+                    for &next in self.pages_after(x) {
+                        if pages.contains(&next) && seen.insert(next) {
+                            stack.push(next);
+                        }
+                    }
+                }
+                seen
+            })
+            .collect();
+        let keep = largest_antichain(update.len(), |i, j| {
+            i > j && reachable[i].contains(&update[j])
+        });
+
+        // sorting with the kept pages chained in their current order leaves
+        // them where they are relative to each other
+        let mut rules: Vec<Rule> = update
             .iter()
-            .filter(|(x, _)| *x == page)
-            .copied()
-            .collect()
+            .flat_map(|&x| self.rules_for(x))
+            .filter(|(_, x)| pages.contains(x))
+            .collect();
+        let kept: Vec<u32> = (0..update.len())
+            .filter(|&i| keep[i])
+            .map(|i| update[i])
+            .collect();
+        rules.extend(kept.windows(2).map(|w| (w[0], w[1])));
+        let sorted = Ruleset::new(rules).sort_update(update)?;
+
+        // each moved page goes right after the page that precedes it in the
+        // sorted update, which is already in place when going in order
+        let mut current = update.clone();
+        let mut moves = vec![];
+        for (rank, &page) in sorted.iter().enumerate() {
+            if kept.contains(&page) {
+                continue;
+            }
+            let from = current.iter().position(|&x| x == page).unwrap();
+            current.remove(from);
+            let to = match rank {
+                0 => 0,
+                _ => current.iter().position(|&x| x == sorted[rank - 1]).unwrap() + 1,
+            };
+            current.insert(to, page);
+            moves.push(Move { page, from, to });
+        }
+
+        Ok(moves)
+    }
+
+    fn explain(&self, update: &Update) -> Result<String, Cycle> {
+        let pages = update
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+        let violations = self.violations(update);
+        if violations.is_empty() {
+            return Ok(format!("{}: valid", pages));
+        }
+
+        let mut lines = vec![format!(
+            "{}: {} {}",
+            pages,
+            violations.len(),
+            if violations.len() == 1 {
+                "violation"
+            } else {
+                "violations"
+            }
+        )];
+        lines.extend(violations.iter().map(|v| format!("  {}", v)));
+        lines.extend(self.fix(update)?.iter().map(|m| format!("  {}", m)));
+
+        Ok(lines.join("\n"))
+    }
+}
+
+#[allow(dead_code)]
+fn largest_antichain(n: usize, less: impl Fn(usize, usize) -> bool) -> Vec<bool> {
+    // `less` has to be a strict partial order; by Dilworth's theorem the
+    // largest antichain is as big as the smallest chain cover, which comes
+    // from a maximum matching, and König's theorem recovers the antichain
+    fn augment(
+        i: usize,
+        edges: &[Vec<usize>],
+        matched: &mut [Option<usize>],
+        seen: &mut [bool],
+    ) -> bool {
+        for &j in edges[i].iter() {
+            if seen[j] {
+                continue;
+            }
+            seen[j] = true;
+            if matched[j].is_none_or(|k| augment(k, edges, matched, seen)) {
+                matched[j] = Some(i);
+                return true;
+            }
+        }
+        false
     }
+
+    let edges: Vec<Vec<usize>> = (0..n)
+        .map(|i| (0..n).filter(|&j| less(i, j)).collect())
+        .collect();
+    let mut matched: Vec<Option<usize>> = vec![None; n];
+    let matched_left: Vec<bool> = (0..n)
+        .map(|i| augment(i, &edges, &mut matched, &mut vec![false; n]))
+        .collect();
+
+    // alternating paths from the unmatched left vertices
+    let mut left = vec![false; n];
+    let mut right = vec![false; n];
+    let mut stack: Vec<usize> = (0..n).filter(|&i| !matched_left[i]).collect();
+    for &i in stack.iter() {
+        left[i] = true;
+    }
+    while let Some(i) = stack.pop() {
+        for &j in edges[i].iter() {
+            if right[j] {
+                continue;
+            }
+            right[j] = true;
+            if let Some(k) = matched[j].filter(|&k| !left[k]) {
+                left[k] = true;
+                stack.push(k);
+            }
+        }
+    }
+
+    (0..n).map(|i| left[i] && !right[i]).collect()
 }
 
 #[aoc(day5, part1)]
@@ -88,15 +393,17 @@ pub fn part1(input: &str) -> u32 {
 }
 
 #[aoc(day5, part2)]
-pub fn part2(input: &str) -> u32 {
+pub fn part2(input: &str) -> Result<u32, String> {
     let (rules, updates) = parse_part1(input);
     let ruleset = Ruleset::new(rules);
 
     updates
         .into_iter()
         .filter(|update| !ruleset.valid_update(update))
-        .map(|update| ruleset.sort_update(&update).unwrap())
-        .map(|update| update[update.len() / 2])
+        .map(|update| {
+            let sorted = ruleset.sort_update(&update)?;
+            Ok(sorted[sorted.len() / 2])
+        })
         .sum()
 }
 
@@ -140,7 +447,7 @@ mod tests {
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(INPUT), 123);
+        assert_eq!(part2(INPUT), Ok(123));
     }
 
     #[test]
@@ -161,7 +468,138 @@ mod tests {
 
         assert_eq!(
             ruleset.sort_update(&vec![75, 97, 47, 61, 53]),
-            Some(vec![97, 75, 47, 61, 53])
+            Ok(vec![97, 75, 47, 61, 53])
+        );
+    }
+
+    #[test]
+    fn sort_keeps_unrelated_pages_in_place() {
+        let ruleset = Ruleset::new(vec![(3, 1)]);
+
+        assert_eq!(
+            ruleset.sort_update(&vec![5, 1, 4, 3, 2]),
+            Ok(vec![5, 4, 3, 1, 2])
+        );
+        assert!(ruleset.valid_update(&vec![5, 3, 4, 1]));
+        assert!(!ruleset.valid_update(&vec![1, 5, 3]));
+    }
+
+    #[test]
+    fn sort_reports_cycles() {
+        let ruleset = Ruleset::new(vec![(1, 2), (2, 3), (3, 1), (4, 1)]);
+        let cycle = ruleset.sort_update(&vec![4, 3, 2, 1]).unwrap_err();
+
+        assert_eq!(cycle.pages, vec![1, 2, 3]);
+        assert_eq!(cycle.to_string(), "Contradictory rules: 1 -> 2 -> 3 -> 1");
+
+        // the cycle only matters when all of its pages are in the update
+        assert_eq!(ruleset.sort_update(&vec![3, 2, 4]), Ok(vec![2, 3, 4]));
+    }
+
+    #[test]
+    fn find_cycle_in_ruleset() {
+        let (rules, _) = parse_part1(INPUT);
+        assert_eq!(Ruleset::new(rules).find_cycle(), None);
+
+        let ruleset = Ruleset::new(vec![(1, 2), (2, 3), (3, 4), (4, 2), (5, 1)]);
+        assert_eq!(
+            ruleset.find_cycle(),
+            Some(Cycle {
+                pages: vec![2, 3, 4]
+            })
         );
     }
+
+    #[test]
+    fn part2_with_contradictory_rules() {
+        let input = "1|2\n2|1\n\n2,1";
+        assert_eq!(
+            part2(input),
+            Err("Contradictory rules: 1 -> 2 -> 1".to_string())
+        );
+    }
+
+    #[test]
+    fn list_violations() {
+        let (rules, _) = parse_part1(INPUT);
+        let ruleset = Ruleset::new(rules);
+
+        assert_eq!(ruleset.violations(&vec![75, 47, 61, 53, 29]), vec![]);
+        assert_eq!(
+            ruleset.violations(&vec![61, 13, 29]),
+            vec![Violation {
+                rule: (29, 13),
+                positions: (2, 1)
+            }]
+        );
+        assert_eq!(
+            ruleset.violations(&vec![75, 97, 47, 61, 53])[0].to_string(),
+            "page 97 (at 1) appears after 75 (at 0) but rule 97|75 requires before"
+        );
+    }
+
+    #[test]
+    fn fix_with_fewest_moves() {
+        let (rules, updates) = parse_part1(INPUT);
+        let ruleset = Ruleset::new(rules);
+
+        for update in updates {
+            let moves = ruleset.fix(&update).unwrap();
+            let mut fixed = update.clone();
+            for m in moves.iter() {
+                assert_eq!(fixed.remove(m.from), m.page);
+                fixed.insert(m.to, m.page);
+            }
+            assert!(ruleset.valid_update(&fixed));
+            assert_eq!(moves.is_empty(), ruleset.valid_update(&update));
+        }
+
+        assert_eq!(ruleset.fix(&vec![75, 47, 61, 53, 29]), Ok(vec![]));
+        assert_eq!(ruleset.fix(&vec![97, 13, 75, 29, 47]).unwrap().len(), 2);
+        assert_eq!(
+            ruleset.fix(&vec![61, 13, 29]),
+            Ok(vec![Move {
+                page: 29,
+                from: 2,
+                to: 1
+            }])
+        );
+    }
+
+    #[test]
+    fn explain_update() {
+        let (rules, _) = parse_part1(INPUT);
+        let ruleset = Ruleset::new(rules);
+
+        assert_eq!(
+            ruleset.explain(&vec![75, 47, 61, 53, 29]),
+            Ok("75,47,61,53,29: valid".to_string())
+        );
+        assert_eq!(
+            ruleset.explain(&vec![61, 13, 29]),
+            Ok("61,13,29: 1 violation
+  page 29 (at 2) appears after 13 (at 1) but rule 29|13 requires before
+  move 29 from 2 to 1"
+                .to_string())
+        );
+    }
+
+    #[test]
+    fn fix_leaves_unrelated_pages_alone() {
+        let ruleset = Ruleset::new(vec![(1, 2), (1, 3)]);
+
+        assert_eq!(
+            ruleset.fix(&vec![3, 2, 9, 1]),
+            Ok(vec![Move {
+                page: 1,
+                from: 3,
+                to: 0
+            }])
+        );
+
+        // 3 only has to come before 1 through 2 when 2 is in the update
+        let ruleset = Ruleset::new(vec![(3, 2), (2, 1)]);
+        assert_eq!(ruleset.fix(&vec![1, 9, 3]).unwrap().len(), 0);
+        assert_eq!(ruleset.fix(&vec![1, 2, 3]).unwrap().len(), 2);
+    }
 }
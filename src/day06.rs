@@ -1,7 +1,10 @@
 use aoc_runner_derive::aoc;
+use rayon::prelude::*;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum Dir {
+mod crowd;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dir {
     North = 0,
     East,
     South,
@@ -11,14 +14,14 @@ enum Dir {
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum Cell {
     Empty,
-    Wall([bool; 4]),
+    Wall,
     Guard(Dir),
 }
 
 impl From<char> for Cell {
     fn from(value: char) -> Self {
         match value {
-            '#' => Self::Wall([false; 4]),
+            '#' => Self::Wall,
             '^' => Self::Guard(Dir::North),
             '>' => Self::Guard(Dir::East),
             'v' => Self::Guard(Dir::South),
@@ -29,22 +32,6 @@ impl From<char> for Cell {
 }
 
 impl Cell {
-    fn add_hit(&self, dir: Dir) -> Option<Cell> {
-        match self {
-            Self::Wall(hits) => {
-                let idx = dir as usize;
-                if hits[idx] {
-                    return None;
-                }
-
-                let mut new_hits = hits.clone();
-                new_hits[idx] = true;
-                Some(Self::Wall(new_hits))
-            }
-            _ => Some(*self),
-        }
-    }
-
     fn is_guard(&self) -> bool {
         match self {
             Self::Guard(_) => true,
@@ -54,7 +41,7 @@ impl Cell {
 
     fn is_obstacle(&self) -> bool {
         match self {
-            Self::Wall(_) => true,
+            Self::Wall => true,
             _ => false,
         }
     }
@@ -74,7 +61,24 @@ impl Cell {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+impl From<Dir> for char {
+    fn from(value: Dir) -> Self {
+        match value {
+            Dir::North => '^',
+            Dir::East => '>',
+            Dir::South => 'v',
+            Dir::West => '<',
+        }
+    }
+}
+
+impl Dir {
+    fn is_vertical(&self) -> bool {
+        matches!(self, Self::North | Self::South)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 struct Guard {
     position: (i32, i32),
     dir: Dir,
@@ -137,23 +141,26 @@ impl From<&str> for Grid {
 
 impl Grid {
     fn guard(&self) -> Guard {
-        let (i, cell) = self
-            .cells
+        self.guards()[0]
+    }
+
+    fn guards(&self) -> Vec<Guard> {
+        self.cells
             .iter()
             .enumerate()
-            .find(|(_, x)| x.is_guard())
-            .unwrap();
-
-        let position = self.xy_for(i);
-        let dir = cell.dir().unwrap();
-
-        Guard { position, dir }
+            .filter(|(_, x)| x.is_guard())
+            .map(|(i, cell)| Guard {
+                position: self.xy_for(i),
+                dir: cell.dir().unwrap(),
+            })
+            .collect()
     }
 
     fn xy(&self, position: (i32, i32)) -> Option<Cell> {
         self.index_for(position).map(|i| self.cells[i])
     }
 
+    #[allow(dead_code)]
     fn set_xy(&mut self, position: (i32, i32), cell: Cell) -> Option<Cell> {
         let i = self.index_for(position)?;
         self.cells[i] = cell;
@@ -189,66 +196,264 @@ impl Grid {
     }
 }
 
-pub struct Patrol {
-    grid: Grid,
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cycle {
+    entry: ((i32, i32), Dir),
+    start: usize,
+    period: usize,
+    cells: Vec<(i32, i32)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RouteEnd {
+    Exit((i32, i32), Dir),
+    Loop(Cycle),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    states: Vec<((i32, i32), Dir)>,
+    end: RouteEnd,
+}
+
+#[allow(dead_code)]
+impl Route {
+    pub fn states(&self) -> &[((i32, i32), Dir)] {
+        &self.states
+    }
+
+    pub fn end(&self) -> &RouteEnd {
+        &self.end
+    }
+
+    pub fn turns(&self) -> Vec<(i32, i32)> {
+        self.states
+            .windows(2)
+            .filter(|pair| pair[0].0 == pair[1].0)
+            .map(|pair| pair[0].0)
+            .collect()
+    }
+
+    pub fn render(&self, grid: &Grid) -> String {
+        // bit 0 marks vertical movement, bit 1 horizontal movement and bit 2
+        // a turn, as in the `|`, `-` and `+` drawings of the puzzle
+        let mut marks = vec![0u8; grid.cells.len()];
+        for (position, dir) in self.states.iter() {
+            let idx = grid.index_for(*position).unwrap();
+            marks[idx] |= if dir.is_vertical() { 1 } else { 2 };
+        }
+        for position in self.turns() {
+            marks[grid.index_for(position).unwrap()] |= 4;
+        }
+
+        let start = self.states[0];
+        let mut out = String::new();
+        for (idx, cell) in grid.cells.iter().enumerate() {
+            let c = match (cell, marks[idx]) {
+                (Cell::Wall, _) => '#',
+                _ if grid.xy_for(idx) == start.0 => start.1.into(),
+                (_, 0) => '.',
+                (_, 1) => '|',
+                (_, 2) => '-',
+                _ => '+',
+            };
+            out.push(c);
+            if idx % grid.width == grid.width - 1 {
+                out.push('\n');
+            }
+        }
+
+        out
+    }
+}
+
+struct JumpTable {
+    width: usize,
+    stops: Vec<Option<(i32, i32)>>,
+}
+
+impl JumpTable {
+    fn new(grid: &Grid) -> Self {
+        // for every (cell, direction), the last cell the guard reaches before
+        // bumping into an obstacle, or `None` if it walks off the map
+        let mut stops = vec![None; grid.cells.len() * 4];
+        for (idx, cell) in grid.cells.iter().enumerate() {
+            if cell.is_obstacle() {
+                continue;
+            }
+
+            for dir in [Dir::North, Dir::East, Dir::South, Dir::West] {
+                let mut guard = Guard {
+                    position: grid.xy_for(idx),
+                    dir,
+                };
+                stops[idx * 4 + dir as usize] = loop {
+                    let next = guard.advance();
+                    match grid.xy(next.position) {
+                        None => break None,
+                        Some(cell) if cell.is_obstacle() => break Some(guard.position),
+                        _ => guard = next,
+                    }
+                };
+            }
+        }
+
+        Self {
+            width: grid.width,
+            stops,
+        }
+    }
+
+    fn stop(&self, guard: Guard) -> Option<(i32, i32)> {
+        let (x, y) = guard.position;
+        self.stops[(y as usize * self.width + x as usize) * 4 + guard.dir as usize]
+    }
+
+    fn has_loop(&self, start: Guard, obstacle: (i32, i32)) -> bool {
+        let mut turns = vec![0u8; self.stops.len() / 4];
+        let mut guard = start;
+
+        loop {
+            let stop = self.stop(guard);
+            let delta = guard.advance().position;
+            let delta = (delta.0 - guard.position.0, delta.1 - guard.position.1);
+            let distance = |(x, y): (i32, i32)| {
+                let (dx, dy) = (x - guard.position.0, y - guard.position.1);
+                if dx * delta.1 != dy * delta.0 {
+                    return None;
+                }
+                Some(dx * delta.0 + dy * delta.1)
+            };
+
+            let max_distance = stop.map(|s| distance(s).unwrap()).unwrap_or(i32::MAX);
+            let position = match distance(obstacle) {
+                Some(d) if d >= 1 && d <= max_distance => {
+                    (obstacle.0 - delta.0, obstacle.1 - delta.1)
+                }
+                _ => match stop {
+                    Some(position) => position,
+                    None => return false,
+                },
+            };
+
+            guard = Guard {
+                position,
+                dir: guard.dir,
+            }
+            .turn();
+
+            let idx = position.1 as usize * self.width + position.0 as usize;
+            let bit = 1 << guard.dir as u8;
+            if turns[idx] & bit != 0 {
+                return true;
+            }
+            turns[idx] |= bit;
+        }
+    }
+}
+
+pub struct Patrol<'a> {
+    grid: &'a Grid,
     guard: Guard,
-    has_loop: Option<bool>,
+    visited: Vec<u8>,
+    path: Vec<Guard>,
+    repeated: Option<Guard>,
+    done: bool,
 }
 
-impl Patrol {
-    pub fn new(grid: Grid) -> Self {
+impl<'a> Patrol<'a> {
+    pub fn new(grid: &'a Grid) -> Self {
         let guard = grid.guard();
-        Self {
+        let mut patrol = Self {
             grid,
             guard,
-            has_loop: None,
-        }
+            visited: vec![0; grid.cells.len()],
+            path: vec![guard],
+            repeated: None,
+            done: false,
+        };
+        patrol.visit(guard);
+
+        patrol
+    }
+
+    pub fn route(&mut self) -> Route {
+        self.run();
+
+        let states = self.path.iter().map(|g| (g.position, g.dir)).collect();
+        let end = match self.repeated {
+            Some(entry) => {
+                let start = self.path.iter().position(|&g| g == entry).unwrap();
+                let mut cells: Vec<(i32, i32)> =
+                    self.path[start..].iter().map(|g| g.position).collect();
+                cells.sort();
+                cells.dedup();
+
+                RouteEnd::Loop(Cycle {
+                    entry: (entry.position, entry.dir),
+                    start,
+                    period: self.path.len() - start,
+                    cells,
+                })
+            }
+            None => RouteEnd::Exit(self.guard.position, self.guard.dir),
+        };
+
+        Route { states, end }
     }
 
     pub fn run(&mut self) -> bool {
-        while self.tick().is_some() {}
+        while self.tick() {}
 
-        self.has_loop.unwrap_or(false)
+        self.repeated.is_some()
     }
 
-    fn tick(&mut self) -> Option<bool> {
+    fn tick(&mut self) -> bool {
+        if self.done {
+            return false;
+        }
+
         let forward_guard = self.guard.advance();
-        if let Some(cell) = self.grid.xy(forward_guard.position) {
-            if cell == Cell::Guard(forward_guard.dir) {
-                self.has_loop = Some(true);
-                return None;
+        let next = match self.grid.xy(forward_guard.position) {
+            None => {
+                self.done = true;
+                return false;
             }
+            Some(cell) if cell.is_obstacle() => self.guard.turn(),
+            Some(_) => forward_guard,
+        };
 
-            self.guard = if cell.is_obstacle() {
-                if let Some(new_cell) = cell.add_hit(self.guard.dir) {
-                    self.grid.set_xy(forward_guard.position, new_cell);
-                    self.guard.turn()
-                } else {
-                    self.has_loop = Some(true);
-                    return None;
-                }
-            } else {
-                forward_guard
-            };
-
-            self.grid
-                .set_xy(self.guard.position, Cell::Guard(self.guard.dir))?;
-            self.has_loop = Some(false);
-            Some(false)
-        } else {
-            None
+        // every (cell, direction) state is deterministic, so stepping into one
+        // we've been in before means the guard is stuck in a loop
+        if !self.visit(next) {
+            self.repeated = Some(next);
+            self.done = true;
+            return false;
         }
+
+        self.guard = next;
+        self.path.push(next);
+        true
+    }
+
+    fn visit(&mut self, guard: Guard) -> bool {
+        let idx = self.grid.index_for(guard.position).unwrap();
+        let bit = 1 << guard.dir as u8;
+        let is_new = self.visited[idx] & bit == 0;
+        self.visited[idx] |= bit;
+
+        is_new
     }
 
     fn route_len(&self) -> usize {
-        self.grid.cells.iter().filter(|x| x.is_guard()).count()
+        self.visited.iter().filter(|&&x| x != 0).count()
     }
 }
 
 #[aoc(day6, part1)]
 fn part1(input: &str) -> u32 {
     let grid = Grid::from(input);
-    let mut patrol = Patrol::new(grid);
+    let mut patrol = Patrol::new(&grid);
 
     patrol.run();
     patrol.route_len() as u32
@@ -257,31 +462,33 @@ fn part1(input: &str) -> u32 {
 #[aoc(day6, part2)]
 fn part2(input: &str) -> u32 {
     let grid = Grid::from(input);
+    let route = Patrol::new(&grid).route();
+    let table = JumpTable::new(&grid);
+
+    // only cells on the original route can change it, and the guard walks
+    // exactly as before until it first reaches the new obstacle
+    let mut tried = vec![false; grid.cells.len()];
+
+    let candidates: Vec<(Guard, (i32, i32))> = route
+        .states
+        .windows(2)
+        .filter_map(|pair| {
+            let obstacle = pair[1].0;
+            let idx = grid.index_for(obstacle).unwrap();
+            if tried[idx] || !grid.cells[idx].is_empty() {
+                return None;
+            }
 
-    let empty_indices =
-        grid.cells
-            .iter()
-            .enumerate()
-            .filter_map(|(idx, cell)| if cell.is_empty() { Some(idx) } else { None });
-
-    let mut patrols = empty_indices
-        .map(|i| {
-            let mut patrol_grid = grid.clone();
-            patrol_grid.cells[i] = Cell::Wall([false; 4]);
-
-            Patrol::new(patrol_grid)
+            tried[idx] = true;
+            let (position, dir) = pair[0];
+            Some((Guard { position, dir }, obstacle))
         })
-        .collect::<Vec<Patrol>>();
+        .collect();
 
-    let mut res = 0;
-    for patrol in patrols.iter_mut() {
-        let has_loop = patrol.run();
-        if has_loop {
-            res += 1;
-        }
-    }
-
-    res
+    candidates
+        .par_iter()
+        .filter(|(guard, obstacle)| table.has_loop(*guard, *obstacle))
+        .count() as u32
 }
 
 #[cfg(test)]
@@ -304,8 +511,157 @@ mod tests {
         assert_eq!(part1(INPUT), 41);
     }
 
+    fn part2_brute_force(input: &str) -> u32 {
+        let grid = Grid::from(input);
+
+        grid.cells
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| cell.is_empty())
+            .filter(|&(i, _)| {
+                let mut patrol_grid = grid.clone();
+                patrol_grid.cells[i] = Cell::Wall;
+                Patrol::new(&patrol_grid).run()
+            })
+            .count() as u32
+    }
+
     #[test]
     fn part2_example() {
         assert_eq!(part2(INPUT), 6);
     }
+
+    #[test]
+    fn part2_matches_brute_force() {
+        let inputs = [
+            INPUT,
+            r"..#..
+.#..#
+.....
+#^...
+...#.",
+            r"...#....
+.......#
+........
+.#^.....
+......#.
+........",
+        ];
+
+        for input in inputs {
+            assert_eq!(part2(input), part2_brute_force(input));
+        }
+    }
+
+    #[test]
+    fn jump_table_stops() {
+        let grid = Grid::from(INPUT);
+        let table = JumpTable::new(&grid);
+        let guard = |position, dir| Guard { position, dir };
+
+        assert_eq!(table.stop(guard((4, 6), Dir::North)), Some((4, 1)));
+        assert_eq!(table.stop(guard((4, 1), Dir::East)), Some((8, 1)));
+        assert_eq!(table.stop(guard((7, 7), Dir::South)), None);
+        assert_eq!(table.stop(guard((2, 4), Dir::North)), Some((2, 4)));
+    }
+
+    #[test]
+    fn route_exits_map() {
+        let grid = Grid::from(INPUT);
+        let mut patrol = Patrol::new(&grid);
+        let route = patrol.route();
+
+        assert_eq!(route.states()[0], ((4, 6), Dir::North));
+        assert_eq!(route.end(), &RouteEnd::Exit((7, 9), Dir::South));
+        assert_eq!(
+            route.turns(),
+            vec![
+                (4, 1),
+                (8, 1),
+                (8, 6),
+                (2, 6),
+                (2, 4),
+                (6, 4),
+                (6, 8),
+                (1, 8),
+                (1, 7),
+                (7, 7)
+            ]
+        );
+    }
+
+    #[test]
+    fn route_with_loop() {
+        let mut grid = Grid::from(INPUT);
+        grid.set_xy((3, 6), Cell::Wall);
+        let mut patrol = Patrol::new(&grid);
+        let route = patrol.route();
+
+        // the guard walks a rectangle back onto its start cell, facing north
+        let RouteEnd::Loop(cycle) = route.end() else {
+            panic!("expected a loop");
+        };
+        assert_eq!(cycle.entry, ((4, 6), Dir::North));
+        assert_eq!(cycle.start, 0);
+        assert_eq!(cycle.period, 22);
+        assert_eq!(cycle.cells.len(), 18);
+        assert!(cycle.cells.contains(&(8, 1)));
+    }
+
+    #[test]
+    fn loop_entered_after_a_detour() {
+        let grid = Grid::from(
+            r"......
+.#....
+.....#
+......
+#.....
+.^..#.",
+        );
+        let mut patrol = Patrol::new(&grid);
+        let route = patrol.route();
+
+        let RouteEnd::Loop(cycle) = route.end() else {
+            panic!("expected a loop");
+        };
+        assert_eq!(cycle.entry, ((1, 4), Dir::North));
+        assert_eq!(cycle.start, 1);
+        assert_eq!(cycle.period, 14);
+        assert_eq!(route.states().len(), cycle.start + cycle.period);
+        assert_eq!(cycle.cells.len(), cycle.period - 4);
+    }
+
+    #[test]
+    fn patrols_share_one_map() {
+        let grid = Grid::from(INPUT);
+        let mut first = Patrol::new(&grid);
+        let mut second = Patrol::new(&grid);
+
+        assert!(!first.run());
+        assert!(!second.run());
+        assert_eq!(first.route_len(), 41);
+        assert_eq!(second.route_len(), 41);
+        assert_eq!(grid, Grid::from(INPUT));
+    }
+
+    #[test]
+    fn render_route() {
+        let mut grid = Grid::from(INPUT);
+        grid.set_xy((3, 6), Cell::Wall);
+        let mut patrol = Patrol::new(&grid);
+        let route = patrol.route();
+
+        let expected = r"....#.....
+....+---+#
+....|...|.
+..#.|...|.
+....|..#|.
+....|...|.
+.#.#^---+.
+........#.
+#.........
+......#...
+";
+        assert_eq!(route.render(&grid), expected);
+    }
 }
@@ -0,0 +1,327 @@
+use super::{Dir, Grid, Guard};
+
+const DIRS: [Dir; 4] = [Dir::North, Dir::East, Dir::South, Dir::West];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub enum TurnPolicy {
+    Right,
+    Left,
+    Reverse,
+    Random(u64),
+}
+
+impl TurnPolicy {
+    fn turn(&mut self, dir: Dir) -> Dir {
+        let steps = match self {
+            Self::Right => 1,
+            Self::Reverse => 2,
+            Self::Left => 3,
+            Self::Random(state) => {
+                // xorshift64, so runs are reproducible for a given seed; it
+                // would be stuck at zero, so that seed is swapped for another
+                if *state == 0 {
+                    *state = 0x9e37_79b9_7f4a_7c15;
+                }
+                *state ^= *state << 13;
+                *state ^= *state >> 7;
+                *state ^= *state << 17;
+                1 + (*state % 3) as usize
+            }
+        };
+
+        DIRS[(dir as usize + steps) % 4]
+    }
+
+    fn is_deterministic(&self) -> bool {
+        !matches!(self, Self::Random(_))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Walking,
+    Looping,
+    Exited,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Collision {
+    tick: usize,
+    guards: (usize, usize),
+    position: (i32, i32),
+}
+
+#[derive(Debug, Clone)]
+struct Walker {
+    guard: Guard,
+    policy: TurnPolicy,
+    visited: Vec<u8>,
+    status: Status,
+}
+
+pub struct Crowd<'a> {
+    grid: &'a Grid,
+    walkers: Vec<Walker>,
+    tick: usize,
+    collisions: Vec<Collision>,
+}
+
+#[allow(dead_code)]
+impl<'a> Crowd<'a> {
+    pub fn new(grid: &'a Grid, policies: &[TurnPolicy]) -> Self {
+        let walkers = grid
+            .guards()
+            .into_iter()
+            .enumerate()
+            .map(|(i, guard)| {
+                let mut visited = vec![0; grid.cells.len()];
+                visited[grid.index_for(guard.position).unwrap()] = 1 << guard.dir as u8;
+
+                Walker {
+                    guard,
+                    policy: policies.get(i).copied().unwrap_or(TurnPolicy::Right),
+                    visited,
+                    status: Status::Walking,
+                }
+            })
+            .collect();
+
+        Self {
+            grid,
+            walkers,
+            tick: 0,
+            collisions: vec![],
+        }
+    }
+
+    pub fn run(&mut self, max_ticks: usize) {
+        // looping guards keep walking so they can still bump into the others,
+        // until every guard has either left the map or settled into a loop
+        while self.tick < max_ticks && self.walkers.iter().any(|w| w.status == Status::Walking) {
+            self.step();
+        }
+    }
+
+    pub fn statuses(&self) -> Vec<Status> {
+        self.walkers.iter().map(|w| w.status).collect()
+    }
+
+    pub fn collisions(&self) -> &[Collision] {
+        &self.collisions
+    }
+
+    pub fn coverage(&self) -> Vec<usize> {
+        self.walkers
+            .iter()
+            .map(|w| w.visited.iter().filter(|&&x| x != 0).count())
+            .collect()
+    }
+
+    pub fn combined_coverage(&self) -> Vec<usize> {
+        (0..self.grid.cells.len())
+            .map(|idx| self.walkers.iter().filter(|w| w.visited[idx] != 0).count())
+            .collect()
+    }
+
+    pub fn render_coverage(&self) -> String {
+        let combined = self.combined_coverage();
+        let mut out = String::new();
+        for (idx, cell) in self.grid.cells.iter().enumerate() {
+            let c = match combined[idx] {
+                _ if cell.is_obstacle() => '#',
+                0 => '.',
+                n if n < 10 => char::from_digit(n as u32, 10).unwrap(),
+                _ => '*',
+            };
+            out.push(c);
+            if idx % self.grid.width == self.grid.width - 1 {
+                out.push('\n');
+            }
+        }
+
+        out
+    }
+
+    fn step(&mut self) {
+        let before: Vec<Guard> = self.walkers.iter().map(|w| w.guard).collect();
+        for walker in self.walkers.iter_mut() {
+            if walker.status != Status::Exited {
+                walker.step(self.grid);
+            }
+        }
+        self.tick += 1;
+
+        for i in 0..self.walkers.len() {
+            for j in i + 1..self.walkers.len() {
+                let (a, b) = (&self.walkers[i], &self.walkers[j]);
+                if a.status == Status::Exited || b.status == Status::Exited {
+                    continue;
+                }
+
+                let same_cell = a.guard.position == b.guard.position;
+                let swapped = a.guard.position == before[j].position
+                    && b.guard.position == before[i].position;
+                if same_cell || swapped {
+                    self.collisions.push(Collision {
+                        tick: self.tick,
+                        guards: (i, j),
+                        position: a.guard.position,
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl Walker {
+    fn step(&mut self, grid: &Grid) {
+        let forward = self.guard.advance();
+        self.guard = match grid.xy(forward.position) {
+            None => {
+                self.status = Status::Exited;
+                return;
+            }
+            Some(cell) if cell.is_obstacle() => Guard {
+                position: self.guard.position,
+                dir: self.policy.turn(self.guard.dir),
+            },
+            Some(_) => forward,
+        };
+
+        let idx = grid.index_for(self.guard.position).unwrap();
+        let bit = 1 << self.guard.dir as u8;
+        if self.visited[idx] & bit != 0 && self.policy.is_deterministic() {
+            self.status = Status::Looping;
+        }
+        self.visited[idx] |= bit;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turn_policies() {
+        assert_eq!(TurnPolicy::Right.turn(Dir::North), Dir::East);
+        assert_eq!(TurnPolicy::Left.turn(Dir::North), Dir::West);
+        assert_eq!(TurnPolicy::Reverse.turn(Dir::East), Dir::West);
+
+        let mut a = TurnPolicy::Random(42);
+        let mut b = TurnPolicy::Random(42);
+        for _ in 0..10 {
+            let dir = a.turn(Dir::South);
+            assert_ne!(dir, Dir::South);
+            assert_eq!(dir, b.turn(Dir::South));
+        }
+
+        let mut zero = TurnPolicy::Random(0);
+        let dirs: Vec<Dir> = (0..10).map(|_| zero.turn(Dir::North)).collect();
+        assert!(dirs.iter().any(|&dir| dir != Dir::East));
+        assert_ne!(zero, TurnPolicy::Random(0));
+    }
+
+    #[test]
+    fn single_guard_matches_patrol() {
+        let grid = Grid::from(
+            r"....#.....
+.........#
+..........
+..#.......
+.......#..
+..........
+.#..^.....
+........#.
+#.........
+......#...",
+        );
+        let mut crowd = Crowd::new(&grid, &[TurnPolicy::Right]);
+        crowd.run(10_000);
+
+        assert_eq!(crowd.statuses(), vec![Status::Exited]);
+        assert_eq!(crowd.coverage(), vec![41]);
+    }
+
+    #[test]
+    fn guards_with_different_policies() {
+        let grid = Grid::from(
+            r"..#..
+.....
+..^..
+.....
+#..<.",
+        );
+        let mut crowd = Crowd::new(&grid, &[TurnPolicy::Left, TurnPolicy::Reverse]);
+        crowd.run(100);
+
+        // the first guard turns left at the wall above and walks off to the
+        // west, the second one bounces back east off the wall in the corner
+        assert_eq!(crowd.statuses(), vec![Status::Exited, Status::Exited]);
+        assert_eq!(crowd.coverage(), vec![4, 4]);
+        assert!(crowd.collisions().is_empty());
+    }
+
+    #[test]
+    fn detect_head_on_collision() {
+        let grid = Grid::from(
+            r".....
+>..<.
+.....",
+        );
+        let mut crowd = Crowd::new(&grid, &[]);
+        crowd.run(100);
+
+        assert_eq!(
+            crowd.collisions(),
+            &[Collision {
+                tick: 2,
+                guards: (0, 1),
+                position: (2, 1)
+            }]
+        );
+    }
+
+    #[test]
+    fn detect_swap_collision() {
+        let grid = Grid::from(
+            r".....
+.><..
+.....",
+        );
+        let mut crowd = Crowd::new(&grid, &[]);
+        crowd.run(1);
+
+        assert_eq!(crowd.collisions().len(), 1);
+        assert_eq!(crowd.collisions()[0].tick, 1);
+    }
+
+    #[test]
+    fn combined_coverage_map() {
+        let grid = Grid::from(
+            r"...
+^.^
+...",
+        );
+        let mut crowd = Crowd::new(&grid, &[]);
+        crowd.run(100);
+
+        assert_eq!(crowd.render_coverage(), "1.1\n1.1\n...\n");
+    }
+
+    #[test]
+    fn random_policy_stops_at_tick_limit() {
+        let grid = Grid::from(
+            r"#####
+#...#
+#.^.#
+#...#
+#####",
+        );
+        let mut crowd = Crowd::new(&grid, &[TurnPolicy::Random(7)]);
+        crowd.run(50);
+
+        assert_eq!(crowd.statuses(), vec![Status::Walking]);
+        assert_eq!(crowd.render_coverage().matches('1').count(), 9);
+    }
+}
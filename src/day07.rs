@@ -1,81 +1,415 @@
+use std::fmt;
+
+#[cfg(not(feature = "u128"))]
+type Num = u64;
+#[cfg(feature = "u128")]
+type Num = u128;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Add,
+    Mul,
+    Concat,
+    Sub,
+    Xor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ApplyError {
+    Overflow(Num, Op, Num),
+    Negative(Num, Num),
+}
+
+impl fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Overflow(a, op, b) => write!(f, "Overflow: {} {} {}", a, op, b),
+            Self::Negative(a, b) => write!(f, "Negative result: {} - {}", a, b),
+        }
+    }
+}
+
+impl From<ApplyError> for String {
+    fn from(value: ApplyError) -> Self {
+        value.to_string()
+    }
+}
+
+const PART1_OPS: [Op; 2] = [Op::Add, Op::Mul];
+const PART2_OPS: [Op; 3] = [Op::Add, Op::Mul, Op::Concat];
+
+impl Op {
+    fn apply(&self, a: Num, b: Num) -> Result<Num, ApplyError> {
+        let result = match self {
+            Self::Add => a.checked_add(b),
+            Self::Mul => a.checked_mul(b),
+            Self::Concat => concat(a, b),
+            Self::Sub => return a.checked_sub(b).ok_or(ApplyError::Negative(a, b)),
+            Self::Xor => Some(a ^ b),
+        };
+
+        result.ok_or(ApplyError::Overflow(a, *self, b))
+    }
+
+    fn undo(&self, result: Num, b: Num) -> Result<Option<Num>, String> {
+        // the left operand `a` such that `a op b == result`, if there is one
+        let a = match self {
+            Self::Add => result.checked_sub(b),
+            // multiplying by zero loses `a`, so it can't be recovered here;
+            // `search` deals with that case on its own
+            Self::Mul => (b != 0 && result.is_multiple_of(b)).then(|| result / b),
+            Self::Concat => match (10 as Num).checked_pow(digits(b)) {
+                Some(shift) => (result % shift == b).then_some(result / shift),
+                // `b` alone is as wide as the type, so nothing can precede it
+                None => (result == b).then_some(0),
+            },
+            Self::Sub => Some(
+                result
+                    .checked_add(b)
+                    .ok_or(format!("Overflow: {} + {}", result, b))?,
+            ),
+            Self::Xor => Some(result ^ b),
+        };
+
+        Ok(a)
+    }
+}
+
+impl TryFrom<&str> for Op {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "+" | "add" => Ok(Self::Add),
+            "*" | "mul" => Ok(Self::Mul),
+            "||" | "concat" => Ok(Self::Concat),
+            "-" | "sub" => Ok(Self::Sub),
+            "^" | "xor" => Ok(Self::Xor),
+            _ => Err(format!("Invalid operator: {}", value)),
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Self::Add => "+",
+            Self::Mul => "*",
+            Self::Concat => "||",
+            Self::Sub => "-",
+            Self::Xor => "^",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+#[allow(dead_code)]
+fn parse_ops(input: &str) -> Result<Vec<Op>, String> {
+    input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|x| !x.is_empty())
+        .map(Op::try_from)
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Expression {
+    numbers: Vec<Num>,
+    ops: Vec<Op>,
+}
+
+#[allow(dead_code)]
+impl Expression {
+    fn evaluate(&self) -> Result<Num, String> {
+        // operators are always evaluated left-to-right, ignoring precedence
+        self.ops
+            .iter()
+            .zip(self.numbers.iter().skip(1))
+            .try_fold(self.numbers[0], |acc, (op, &x)| op.apply(acc, x))
+            .map_err(String::from)
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.numbers[0])?;
+        for (op, x) in self.ops.iter().zip(self.numbers.iter().skip(1)) {
+            write!(f, " {} {}", op, x)?;
+        }
+        Ok(())
+    }
+}
+
 struct Equation {
-    target: u64,
-    numbers: Vec<u64>,
+    target: Num,
+    numbers: Vec<Num>,
 }
 
 impl Equation {
-    fn solvable1(&self) -> bool {
-        solve_partial_part1(self.target, &self.numbers, None)
+    fn solvable1(&self) -> Result<bool, String> {
+        Ok(self.solve(&PART1_OPS)?.is_some())
+    }
+
+    fn solvable2(&self) -> Result<bool, String> {
+        Ok(self.solve(&PART2_OPS)?.is_some())
     }
 
-    fn solvable2(&self) -> bool {
-        solve_partial_part2(self.target, &self.numbers, None)
+    fn solve(&self, ops: &[Op]) -> Result<Option<Expression>, String> {
+        let mut found = None;
+        self.search(ops, &mut |path| {
+            found = Some(path.to_vec());
+            true
+        })?;
+
+        Ok(found.map(|ops| self.expression(ops)))
+    }
+
+    fn expression(&self, ops: Vec<Op>) -> Expression {
+        Expression {
+            numbers: self.numbers.clone(),
+            ops,
+        }
+    }
+
+    fn search(&self, ops: &[Op], on_found: &mut impl FnMut(&[Op]) -> bool) -> Result<(), String> {
+        if self.numbers.is_empty() {
+            return Ok(());
+        }
+
+        // the search runs from the last operand back, so the operators it
+        // collects come out reversed
+        let mut path = Vec::with_capacity(self.numbers.len() - 1);
+        search(
+            self.target,
+            &self.numbers,
+            ops,
+            &mut path,
+            &mut |reversed| {
+                let ops: Vec<Op> = reversed.iter().rev().copied().collect();
+                on_found(&ops)
+            },
+        )
+        .map_err(|err| format!("{}: {}", self, err))?;
+
+        Ok(())
     }
 }
 
-fn solve_partial_part1(target: u64, numbers: &[u64], acc: Option<u64>) -> bool {
-    if acc.unwrap_or(0) > target {
-        return false;
+#[allow(dead_code)]
+impl Equation {
+    fn solutions(&self, ops: &[Op]) -> Result<Vec<Expression>, String> {
+        let mut found = vec![];
+        self.search(ops, &mut |path| {
+            found.push(path.to_vec());
+            false
+        })?;
+
+        Ok(found.into_iter().map(|ops| self.expression(ops)).collect())
     }
 
-    if numbers.len() == 0 {
-        return acc.unwrap_or(0) == target;
+    fn count_solutions(&self, ops: &[Op]) -> Result<usize, String> {
+        let mut count = 0;
+        self.search(ops, &mut |_| {
+            count += 1;
+            false
+        })?;
+
+        Ok(count)
     }
+}
 
-    solve_partial_part1(target, &numbers[1..], Some(acc.unwrap_or(0) + numbers[0]))
-        || solve_partial_part1(target, &numbers[1..], Some(acc.unwrap_or(1) * numbers[0]))
+impl fmt::Display for Equation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.target)?;
+        for x in self.numbers.iter() {
+            write!(f, " {}", x)?;
+        }
+        Ok(())
+    }
 }
 
-fn concat(a: u64, b: u64) -> u64 {
-    format!("{}{}", a, b).parse().unwrap()
+fn search(
+    target: Num,
+    numbers: &[Num],
+    ops: &[Op],
+    path: &mut Vec<Op>,
+    on_found: &mut impl FnMut(&[Op]) -> bool,
+) -> Result<bool, String> {
+    // returns true once the callback asks to stop searching; an overflow on
+    // any branch fails the whole search, since it can't be ruled out
+    let Some((&x, rest)) = numbers.split_last() else {
+        return Ok(false);
+    };
+    if rest.is_empty() {
+        return Ok(x == target && on_found(path));
+    }
+
+    for &op in ops {
+        if op == Op::Mul && x == 0 {
+            // anything times zero is zero, so every way of evaluating the
+            // operands before it is a solution
+            if target != 0 {
+                continue;
+            }
+            path.push(op);
+            let stop = each_prefix(rest, ops, path, on_found)?;
+            path.pop();
+            if stop {
+                return Ok(true);
+            }
+            continue;
+        }
+
+        let Some(previous) = op.undo(target, x)? else {
+            continue;
+        };
+        path.push(op);
+        let stop = search(previous, rest, ops, path, on_found)?;
+        path.pop();
+        if stop {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
 }
 
-fn solve_partial_part2(target: u64, numbers: &[u64], acc: Option<u64>) -> bool {
-    if acc.unwrap_or(0) > target {
-        return false;
+fn each_prefix(
+    numbers: &[Num],
+    ops: &[Op],
+    path: &mut Vec<Op>,
+    on_found: &mut impl FnMut(&[Op]) -> bool,
+) -> Result<bool, String> {
+    // walks forwards through every operator sequence that evaluates, adding
+    // it to the reversed path of the backwards search; a negative result
+    // just rules a branch out, but an overflow fails like it does in `search`
+    fn walk(
+        acc: Num,
+        numbers: &[Num],
+        ops: &[Op],
+        prefix: &mut Vec<Op>,
+        on_valid: &mut impl FnMut(&[Op]) -> bool,
+    ) -> Result<bool, String> {
+        let Some((&x, rest)) = numbers.split_first() else {
+            return Ok(on_valid(prefix));
+        };
+
+        for &op in ops {
+            let next = match op.apply(acc, x) {
+                Ok(next) => next,
+                Err(ApplyError::Negative(..)) => continue,
+                Err(err) => return Err(err.into()),
+            };
+            prefix.push(op);
+            let stop = walk(next, rest, ops, prefix, on_valid)?;
+            prefix.pop();
+            if stop {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
     }
 
-    if numbers.len() == 0 {
-        return acc.unwrap_or(0) == target;
+    let Some((&first, rest)) = numbers.split_first() else {
+        return Ok(false);
+    };
+    let mut prefix = Vec::with_capacity(rest.len());
+    walk(first, rest, ops, &mut prefix, &mut |prefix| {
+        let len = path.len();
+        path.extend(prefix.iter().rev());
+        let stop = on_found(path);
+        path.truncate(len);
+        stop
+    })
+}
+
+fn digits(x: Num) -> u32 {
+    x.checked_ilog10().unwrap_or(0) + 1
+}
+
+fn concat(a: Num, b: Num) -> Option<Num> {
+    if a == 0 {
+        return Some(b);
     }
+    (10 as Num)
+        .checked_pow(digits(b))?
+        .checked_mul(a)?
+        .checked_add(b)
+}
 
-    solve_partial_part2(target, &numbers[1..], Some(acc.unwrap_or(0) + numbers[0]))
-        || solve_partial_part2(target, &numbers[1..], Some(acc.unwrap_or(1) * numbers[0]))
-        || solve_partial_part2(
-            target,
-            &numbers[1..],
-            Some(concat(acc.unwrap_or(0), numbers[0])),
-        )
+#[allow(dead_code)]
+fn report(equations: &[Equation], ops: &[Op]) -> String {
+    equations
+        .iter()
+        .map(|equation| match equation.solutions(ops) {
+            Ok(solutions) => match solutions.first() {
+                Some(expression) => format!(
+                    "{} = {} ({} {})",
+                    equation.target,
+                    expression,
+                    solutions.len(),
+                    if solutions.len() == 1 {
+                        "solution"
+                    } else {
+                        "solutions"
+                    }
+                ),
+                None => format!("{}: no solution", equation.target),
+            },
+            Err(err) => format!("error in {}", err),
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
 }
 
 use aoc_runner_derive::{aoc, aoc_generator};
 #[aoc_generator(day7)]
-fn parse(input: &str) -> Vec<Equation> {
+fn parse(input: &str) -> Result<Vec<Equation>, String> {
     input
         .lines()
         .map(|line| {
-            let chunks = line.split(": ").collect::<Vec<&str>>();
-            let target = chunks[0].parse().unwrap();
-            let numbers = chunks[1].split(" ").map(|x| x.parse().unwrap()).collect();
-            Equation { target, numbers }
+            let (target, numbers) = line
+                .split_once(": ")
+                .ok_or(format!("Invalid equation: {}", line))?;
+            let target = parse_number(target)?;
+            let numbers = numbers
+                .split(" ")
+                .map(parse_number)
+                .collect::<Result<Vec<Num>, String>>()?;
+            Ok(Equation { target, numbers })
         })
         .collect()
 }
 
+fn parse_number(value: &str) -> Result<Num, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid number: {}", value))
+}
+
 #[aoc(day7, part1)]
-fn part1(input: &[Equation]) -> u64 {
-    input
-        .into_iter()
-        .filter_map(|x| if x.solvable1() { Some(x.target) } else { None })
-        .sum()
+fn part1(input: &[Equation]) -> Result<Num, String> {
+    calibration(input, Equation::solvable1)
 }
 
 #[aoc(day7, part2)]
-fn part2(input: &[Equation]) -> u64 {
-    input
-        .into_iter()
-        .filter_map(|x| if x.solvable2() { Some(x.target) } else { None })
-        .sum()
+fn part2(input: &[Equation]) -> Result<Num, String> {
+    calibration(input, Equation::solvable2)
+}
+
+fn calibration(
+    input: &[Equation],
+    solvable: impl Fn(&Equation) -> Result<bool, String>,
+) -> Result<Num, String> {
+    input.iter().try_fold(0 as Num, |total, equation| {
+        if !solvable(equation)? {
+            return Ok(total);
+        }
+        total
+            .checked_add(equation.target)
+            .ok_or("Overflow adding up the calibration result".to_string())
+    })
 }
 
 #[cfg(test)]
@@ -94,11 +428,266 @@ mod tests {
 
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(INPUT)), 3749);
+        assert_eq!(part1(&parse(INPUT).unwrap()), Ok(3749));
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse(INPUT)), 11387);
+        assert_eq!(part2(&parse(INPUT).unwrap()), Ok(11387));
+    }
+
+    #[test]
+    fn solve_returns_expression() {
+        let equation = Equation {
+            target: 3267,
+            numbers: vec![81, 40, 27],
+        };
+        let expression = equation.solve(&PART1_OPS).unwrap().unwrap();
+
+        assert_eq!(expression.to_string(), "81 * 40 + 27");
+        assert_eq!(expression.evaluate(), Ok(3267));
+    }
+
+    #[test]
+    fn list_and_count_solutions() {
+        let equation = Equation {
+            target: 3267,
+            numbers: vec![81, 40, 27],
+        };
+        let solutions: Vec<String> = equation
+            .solutions(&PART1_OPS)
+            .unwrap()
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+
+        assert_eq!(solutions, vec!["81 * 40 + 27", "81 + 40 * 27"]);
+        assert_eq!(equation.count_solutions(&PART1_OPS), Ok(2));
+
+        let equation = Equation {
+            target: 83,
+            numbers: vec![17, 5],
+        };
+        assert_eq!(equation.solve(&PART2_OPS), Ok(None));
+        assert_eq!(equation.count_solutions(&PART2_OPS), Ok(0));
+    }
+
+    #[test]
+    fn concat_expression() {
+        let equation = Equation {
+            target: 7290,
+            numbers: vec![6, 8, 6, 15],
+        };
+        let expression = equation.solve(&PART2_OPS).unwrap().unwrap();
+
+        assert_eq!(expression.to_string(), "6 * 8 || 6 * 15");
+        assert_eq!(expression.evaluate(), Ok(7290));
+    }
+
+    #[test]
+    fn report_equations() {
+        let equations = parse("190: 10 19\n83: 17 5\n4: 2 2").unwrap();
+
+        assert_eq!(
+            report(&equations, &PART1_OPS),
+            "190 = 10 * 19 (1 solution)\n83: no solution\n4 = 2 + 2 (2 solutions)"
+        );
+    }
+
+    #[test]
+    fn undo_operators() {
+        assert_eq!(Op::Add.undo(190, 19), Ok(Some(171)));
+        assert_eq!(Op::Add.undo(10, 19), Ok(None));
+        assert_eq!(Op::Mul.undo(190, 19), Ok(Some(10)));
+        assert_eq!(Op::Mul.undo(191, 19), Ok(None));
+        assert_eq!(Op::Concat.undo(156, 6), Ok(Some(15)));
+        assert_eq!(Op::Concat.undo(156, 56), Ok(Some(1)));
+        assert_eq!(Op::Concat.undo(156, 156), Ok(Some(0)));
+        assert_eq!(Op::Concat.undo(156, 7), Ok(None));
+        assert_eq!(Op::Concat.undo(100, 0), Ok(Some(10)));
+        assert_eq!(Op::Sub.undo(5, 3), Ok(Some(8)));
+        assert_eq!(Op::Xor.undo(6, 3), Ok(Some(5)));
+    }
+
+    #[test]
+    fn concat_numbers() {
+        assert_eq!(concat(15, 6), Some(156));
+        assert_eq!(concat(12, 345), Some(12345));
+        assert_eq!(concat(1, 0), Some(10));
+        assert_eq!(concat(0, 7), Some(7));
+    }
+
+    #[test]
+    fn parse_operator_sets() {
+        assert_eq!(parse_ops("+ * ||"), Ok(PART2_OPS.to_vec()));
+        assert_eq!(
+            parse_ops("add,sub,xor"),
+            Ok(vec![Op::Add, Op::Sub, Op::Xor])
+        );
+        assert_eq!(parse_ops("+ /"), Err("Invalid operator: /".to_string()));
+    }
+
+    #[test]
+    fn solve_with_sub_and_xor() {
+        let equation = Equation {
+            target: 5,
+            numbers: vec![10, 4, 3],
+        };
+        let ops = [Op::Sub, Op::Xor];
+        let solutions: Vec<String> = equation
+            .solutions(&ops)
+            .unwrap()
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+
+        assert_eq!(solutions, vec!["10 - 4 ^ 3"]);
+        assert_eq!(equation.solve(&PART2_OPS), Ok(None));
+
+        // intermediate results below zero aren't allowed
+        let equation = Equation {
+            target: 1,
+            numbers: vec![2, 3, 2],
+        };
+        assert_eq!(equation.solve(&[Op::Sub, Op::Add]), Ok(None));
+    }
+
+    #[test]
+    fn overflow_is_an_error() {
+        assert_eq!(
+            Op::Mul.apply(Num::MAX, 2),
+            Err(ApplyError::Overflow(Num::MAX, Op::Mul, 2))
+        );
+        assert_eq!(
+            Op::Mul.apply(Num::MAX, 2).map_err(String::from),
+            Err(format!("Overflow: {} * 2", Num::MAX))
+        );
+        assert_eq!(Op::Sub.apply(1, 2), Err(ApplyError::Negative(1, 2)));
+        assert_eq!(concat(Num::MAX, 1), None);
+        assert_eq!(Op::Concat.undo(Num::MAX, Num::MAX), Ok(Some(0)));
+
+        let equation = Equation {
+            target: Num::MAX,
+            numbers: vec![1, 2],
+        };
+        assert_eq!(
+            equation.solve(&[Op::Sub]),
+            Err(format!("{}: 1 2: Overflow: {} + 2", Num::MAX, Num::MAX))
+        );
+        assert_eq!(
+            report(&[equation], &[Op::Sub]),
+            format!("error in {}: 1 2: Overflow: {} + 2", Num::MAX, Num::MAX)
+        );
+
+        let expression = Expression {
+            numbers: vec![Num::MAX, 1],
+            ops: vec![Op::Concat],
+        };
+        assert!(expression.evaluate().is_err());
+    }
+
+    #[test]
+    fn calibration_overflow_is_an_error() {
+        let equations = vec![
+            Equation {
+                target: Num::MAX,
+                numbers: vec![Num::MAX],
+            },
+            Equation {
+                target: 1,
+                numbers: vec![1],
+            },
+        ];
+
+        assert_eq!(
+            part1(&equations),
+            Err("Overflow adding up the calibration result".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_invalid_input() {
+        assert_eq!(
+            parse("190 10 19").err(),
+            Some("Invalid equation: 190 10 19".to_string())
+        );
+        assert_eq!(
+            parse("190: 10 x").err(),
+            Some("Invalid number: x".to_string())
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "u128"))]
+    fn parse_target_too_big() {
+        assert_eq!(
+            parse("18446744073709551616: 1 2").err(),
+            Some("Invalid number: 18446744073709551616".to_string())
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "u128")]
+    fn solve_wide_targets() {
+        let equations = parse("18446744073709551616: 18446744073709551615 1").unwrap();
+        assert_eq!(part1(&equations), Ok(18446744073709551616));
+    }
+
+    #[test]
+    fn multiply_by_zero() {
+        let equations = parse("7: 5 0 7\n0: 5 0").unwrap();
+        assert_eq!(part1(&equations), Ok(7));
+        assert_eq!(
+            equations[0].solve(&PART1_OPS).unwrap().unwrap().to_string(),
+            "5 * 0 + 7"
+        );
+        assert_eq!(equations[0].count_solutions(&PART1_OPS), Ok(1));
+        assert_eq!(
+            equations[1].solve(&PART2_OPS).unwrap().unwrap().to_string(),
+            "5 * 0"
+        );
+        assert_eq!(equations[1].count_solutions(&PART2_OPS), Ok(1));
+
+        // any way of combining the operands before the zero works, as long
+        // as it can be evaluated
+        let equation = Equation {
+            target: 0,
+            numbers: vec![1, 2, 0],
+        };
+        let solutions: Vec<String> = equation
+            .solutions(&PART2_OPS)
+            .unwrap()
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        assert_eq!(solutions, vec!["1 + 2 * 0", "1 * 2 * 0", "1 || 2 * 0"]);
+        assert_eq!(equation.count_solutions(&[Op::Sub, Op::Mul]), Ok(1));
+    }
+
+    #[test]
+    fn overflow_before_multiplying_by_zero() {
+        // the operands before the zero still have to be evaluated, so an
+        // overflow there can't quietly drop the line
+        let equations = parse("5: 18446744073709551615 2 0 5").unwrap();
+        if cfg!(feature = "u128") {
+            assert_eq!(
+                equations[0].solve(&PART1_OPS).unwrap().unwrap().to_string(),
+                "18446744073709551615 + 2 * 0 + 5"
+            );
+        } else {
+            assert_eq!(
+                part1(&equations),
+                Err(format!("5: {} 2 0 5: Overflow: {} + 2", Num::MAX, Num::MAX))
+            );
+        }
+
+        let equation = Equation {
+            target: 0,
+            numbers: vec![Num::MAX, 2, 0],
+        };
+        assert_eq!(
+            equation.count_solutions(&PART1_OPS),
+            Err(format!("0: {} 2 0: Overflow: {} + 2", Num::MAX, Num::MAX))
+        );
     }
 }
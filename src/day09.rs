@@ -1,118 +1,332 @@
 type Block = Option<usize>;
 
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
 use aoc_runner_derive::{aoc, aoc_generator};
-#[aoc_generator(day9)]
-fn parse(input: &str) -> Vec<Block> {
-    input
-        .chars()
-        .map(|x| x.to_string().parse::<usize>().unwrap())
-        .enumerate()
-        .map(|(i, x)| {
-            let block = if i % 2 == 0 { Some(i / 2) } else { None };
-            vec![block; x as usize]
-        })
-        .flatten()
-        .collect()
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Span {
+    id: usize,
+    start: usize,
+    len: usize,
 }
 
-#[aoc(day9, part1)]
-fn part1(input: &[Block]) -> usize {
-    let mut disk = input.to_owned();
-
-    for i in (0..disk.len()).rev() {
-        for j in 0..i {
-            if disk[j].is_none() {
-                disk[j] = disk[i];
-                disk[i] = None;
-                break;
+impl Span {
+    fn new(id: usize, start: usize, len: usize) -> Self {
+        Self { id, start, len }
+    }
+
+    fn checksum(&self) -> usize {
+        // id * (start + (start + 1) + ... + (start + len - 1))
+        self.id * (self.len * self.start + self.len * self.len.saturating_sub(1) / 2)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Disk {
+    files: Vec<Span>,
+    free: Vec<(usize, usize)>,
+    size: usize,
+}
+
+impl Disk {
+    fn from_files(mut files: Vec<Span>, size: usize) -> Self {
+        // empty files take no blocks, but they are kept so the disk can
+        // still be encoded back to the dense format
+        files.sort_by_key(|f| f.start);
+
+        let mut free = vec![];
+        let mut cursor = 0;
+        for file in files.iter().filter(|f| f.len > 0) {
+            if file.start > cursor {
+                free.push((cursor, file.start - cursor));
             }
+            cursor = file.start + file.len;
         }
+        if size > cursor {
+            free.push((cursor, size - cursor));
+        }
+
+        Self { files, free, size }
     }
 
-    checksum(&disk)
-}
+    fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![None; self.size];
+        for file in self.files.iter().filter(|f| f.len > 0) {
+            blocks[file.start..file.start + file.len].fill(Some(file.id));
+        }
 
-fn checksum(disk: &[Block]) -> usize {
-    disk.iter()
-        .enumerate()
-        .map(|(i, x)| i * x.unwrap_or(0))
-        .sum()
+        blocks
+    }
+
+    fn checksum(&self) -> usize {
+        self.files
+            .iter()
+            .filter(|f| f.len > 0)
+            .map(Span::checksum)
+            .sum()
+    }
+
+    fn compact(&self, strategy: &impl CompactionStrategy) -> Compaction {
+        strategy.compact(self)
+    }
 }
 
-#[aoc(day9, part2)]
-fn part2(input: &[Block]) -> usize {
-    let mut disk = input.to_owned();
+#[derive(Debug, PartialEq, Clone)]
+pub struct Compaction {
+    disk: Disk,
+    moves: usize,
+    blocks_moved: usize,
+}
 
-    let count = disk
-        .iter()
-        .rev()
-        .find_map(|x| if x.is_some() { Some(x.unwrap()) } else { None })
-        .unwrap_or(0);
+impl Compaction {
+    fn new(files: Vec<Span>, size: usize, moved: &[Span]) -> Self {
+        Self {
+            disk: Disk::from_files(files, size),
+            moves: moved.len(),
+            blocks_moved: moved.iter().map(|span| span.len).sum(),
+        }
+    }
 
-    for id in (0..=count).rev() {
-        defrag(&mut disk, id);
+    fn checksum(&self) -> usize {
+        self.disk.checksum()
     }
+}
 
-    checksum(&disk)
+pub trait CompactionStrategy {
+    fn compact(&self, disk: &Disk) -> Compaction;
 }
 
-fn defrag(disk: &mut Vec<Block>, id: usize) {
-    let chunks = disk_chunks(&disk).collect::<Vec<Chunk>>();
-    let target_chunk = chunks.iter().rev().find(|x| x.block == Some(id)).unwrap();
-    if let Some(free_chunk) = chunks
-        .iter()
-        .find(|x| x.block.is_none() && x.len >= target_chunk.len && x.index < target_chunk.index)
-    {
-        let free_range = free_chunk.index..free_chunk.index + target_chunk.len;
-        let target_range = target_chunk.index..target_chunk.index + target_chunk.len;
+pub struct BlockWise;
+
+pub struct FirstFit;
+
+#[allow(dead_code)]
+pub struct BestFit;
+
+#[allow(dead_code)]
+pub struct TowardsEnd;
+
+impl CompactionStrategy for BlockWise {
+    fn compact(&self, disk: &Disk) -> Compaction {
+        // fill gaps from the left with blocks taken from the rightmost file
+        let mut files = disk.files.clone();
+        let mut moved = vec![];
+        let mut empty = vec![];
 
-        for (i, j) in free_range.zip(target_range) {
-            disk.swap(i, j);
+        'gaps: for &(start, len) in disk.free.iter() {
+            let (mut start, mut len) = (start, len);
+            while len > 0 {
+                let Some(last) = files.last_mut() else {
+                    break 'gaps;
+                };
+                if last.len == 0 {
+                    // nothing to move, but the file stays on the disk
+                    empty.extend(files.pop());
+                    continue;
+                }
+                if last.start < start {
+                    break 'gaps;
+                }
+
+                let n = len.min(last.len);
+                moved.push(Span::new(last.id, start, n));
+                last.len -= n;
+                start += n;
+                len -= n;
+
+                if last.len == 0 {
+                    files.pop();
+                }
+            }
         }
+
+        let mut result = files;
+        result.extend(moved.iter().copied());
+        result.extend(empty);
+        Compaction::new(result, disk.size, &moved)
     }
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
-struct Chunk {
-    block: Block,
-    len: usize,
-    index: usize,
+impl CompactionStrategy for FirstFit {
+    fn compact(&self, disk: &Disk) -> Compaction {
+        move_files_left(disk, |candidates| {
+            candidates.min_by_key(|&(start, _)| start)
+        })
+    }
 }
 
-impl Chunk {
-    fn new(block: Block, len: usize, index: usize) -> Self {
-        Self { block, len, index }
+impl CompactionStrategy for BestFit {
+    fn compact(&self, disk: &Disk) -> Compaction {
+        move_files_left(disk, |candidates| candidates.next())
     }
 }
 
-struct ChunkIterator<'a> {
-    idx: usize,
-    disk: &'a [Block],
+impl CompactionStrategy for TowardsEnd {
+    fn compact(&self, disk: &Disk) -> Compaction {
+        // mirror image of first fit: files in id order move to the end of the
+        // rightmost gap after them that fits
+        let mut gaps: Vec<BinaryHeap<usize>> = vec![BinaryHeap::new(); max_gap(disk) + 1];
+        for &(start, len) in disk.free.iter() {
+            gaps[len].push(start);
+        }
+
+        let mut files = disk.files.clone();
+        let mut moved = vec![];
+        for file in files.iter_mut().filter(|f| f.len > 0) {
+            let best = (file.len..gaps.len())
+                .filter_map(|len| gaps[len].peek().map(|&start| (start, len)))
+                .filter(|&(start, _)| start > file.start)
+                .max();
+
+            if let Some((start, len)) = best {
+                gaps[len].pop();
+                if len > file.len {
+                    gaps[len - file.len].push(start);
+                }
+                file.start = start + len - file.len;
+                moved.push(*file);
+            }
+        }
+
+        Compaction::new(files, disk.size, &moved)
+    }
 }
 
-impl<'a> Iterator for ChunkIterator<'a> {
-    type Item = Chunk;
+fn move_files_left<F>(disk: &Disk, choose: F) -> Compaction
+where
+    F: Fn(&mut dyn Iterator<Item = (usize, usize)>) -> Option<(usize, usize)>,
+{
+    // free gaps indexed by length, each heap yielding its leftmost gap, and
+    // files moved in descending id order
+    let mut gaps: Vec<BinaryHeap<Reverse<usize>>> = vec![BinaryHeap::new(); max_gap(disk) + 1];
+    for &(start, len) in disk.free.iter() {
+        gaps[len].push(Reverse(start));
+    }
+
+    let mut files = disk.files.clone();
+    let mut moved = vec![];
+    for file in files.iter_mut().rev().filter(|f| f.len > 0) {
+        let mut candidates = (file.len..gaps.len())
+            .filter_map(|len| gaps[len].peek().map(|&Reverse(start)| (start, len)))
+            .filter(|&(start, _)| start < file.start);
 
-    fn next(&mut self) -> Option<Chunk> {
-        let block = self.disk.get(self.idx)?;
-        let mut i = self.idx + 1;
-        while let Some(other_block) = self.disk.get(i) {
-            if other_block == block {
-                i += 1;
-            } else {
-                break;
+        if let Some((start, len)) = choose(&mut candidates) {
+            gaps[len].pop();
+            if len > file.len {
+                gaps[len - file.len].push(Reverse(start + file.len));
             }
+            file.start = start;
+            moved.push(*file);
         }
+    }
+
+    Compaction::new(files, disk.size, &moved)
+}
+
+fn max_gap(disk: &Disk) -> usize {
+    disk.free.iter().map(|&(_, len)| len).max().unwrap_or(0)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Fragmentation {
+    histogram: Vec<usize>,
+    free: usize,
+    largest_gap: Option<(usize, usize)>,
+}
+
+#[allow(dead_code)]
+impl Disk {
+    pub fn encode(&self) -> Result<String, String> {
+        // the dense format alternates file and free lengths, so it can only
+        // describe layouts where every file is whole and in id order
+        let mut out = String::new();
+        let mut cursor = 0;
+
+        for (i, file) in self.files.iter().enumerate() {
+            if file.id != i {
+                return Err(format!("file {} found where file {} expected", file.id, i));
+            }
+            if i > 0 {
+                let gap = file
+                    .start
+                    .checked_sub(cursor)
+                    .ok_or(format!("file {} overlaps the file before it", file.id))?;
+                out.push(digit(gap)?);
+            }
+            out.push(digit(file.len)?);
+            cursor = file.start + file.len;
+        }
+
+        if self.size > cursor {
+            out.push(digit(self.size - cursor)?);
+        }
+
+        Ok(out)
+    }
+
+    pub fn fragmentation(&self) -> Fragmentation {
+        let max_len = self.free.iter().map(|&(_, len)| len).max().unwrap_or(0);
+        let mut histogram = vec![0; max_len + 1];
+        for &(_, len) in self.free.iter() {
+            histogram[len] += 1;
+        }
+
+        Fragmentation {
+            histogram,
+            free: self.free.iter().map(|&(_, len)| len).sum(),
+            largest_gap: self.free.iter().copied().rev().max_by_key(|&(_, len)| len),
+        }
+    }
+}
+
+impl std::fmt::Display for Disk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // ids wrap around past 9, as the puzzle notation has a single digit
+        let layout: String = self
+            .blocks()
+            .into_iter()
+            .map(|block| match block {
+                Some(id) => char::from_digit((id % 10) as u32, 10).unwrap(),
+                None => '.',
+            })
+            .collect();
+
+        write!(f, "{}", layout)
+    }
+}
 
-        let chunk = Chunk::new(*block, i - self.idx, self.idx);
-        self.idx = i;
+fn digit(len: usize) -> Result<char, String> {
+    char::from_digit(len as u32, 10)
+        .filter(|_| len < 10)
+        .ok_or(format!("length {} doesn't fit in a single digit", len))
+}
+
+#[aoc_generator(day9)]
+fn parse(input: &str) -> Disk {
+    let mut files = vec![];
+    let mut position = 0;
 
-        Some(chunk)
+    for (i, len) in input.chars().filter_map(|x| x.to_digit(10)).enumerate() {
+        let len = len as usize;
+        if i % 2 == 0 {
+            files.push(Span::new(i / 2, position, len));
+        }
+        position += len;
     }
+
+    Disk::from_files(files, position)
 }
 
-fn disk_chunks<'a>(disk: &'a [Block]) -> ChunkIterator<'a> {
-    ChunkIterator { idx: 0, disk }
+#[aoc(day9, part1)]
+fn part1(input: &Disk) -> usize {
+    input.compact(&BlockWise).checksum()
+}
+
+#[aoc(day9, part2)]
+fn part2(input: &Disk) -> usize {
+    input.compact(&FirstFit).checksum()
 }
 
 #[cfg(test)]
@@ -121,46 +335,166 @@ mod tests {
 
     const INPUT: &str = "2333133121414131402";
 
-    #[test]
-    fn parse_input() {
-        let expected = "00...111...2...333.44.5555.6666.777.888899"
+    fn to_blocks(layout: &str) -> Vec<Block> {
+        layout
             .chars()
             .map(|x| x.to_string().parse().ok())
-            .collect::<Vec<Block>>();
+            .collect::<Vec<Block>>()
+    }
 
-        assert_eq!(parse(INPUT), expected)
+    #[test]
+    fn parse_input() {
+        let expected = to_blocks("00...111...2...333.44.5555.6666.777.888899");
+
+        assert_eq!(parse(INPUT).blocks(), expected)
     }
 
     #[test]
-    fn iterate_chunks() {
+    fn parse_spans() {
         let disk = parse(INPUT);
-        let chunks = disk_chunks(&disk).collect::<Vec<Chunk>>();
         // "00...111...2...333.44.5555.6666.777.888899"
         assert_eq!(
-            chunks,
-            vec![
-                Chunk::new(Some(0), 2, 0),
-                Chunk::new(None, 3, 2),
-                Chunk::new(Some(1), 3, 5),
-                Chunk::new(None, 3, 8),
-                Chunk::new(Some(2), 1, 11),
-                Chunk::new(None, 3, 12),
-                Chunk::new(Some(3), 3, 15),
-                Chunk::new(None, 1, 18),
-                Chunk::new(Some(4), 2, 19),
-                Chunk::new(None, 1, 21),
-                Chunk::new(Some(5), 4, 22),
-                Chunk::new(None, 1, 26),
-                Chunk::new(Some(6), 4, 27),
-                Chunk::new(None, 1, 31),
-                Chunk::new(Some(7), 3, 32),
-                Chunk::new(None, 1, 35),
-                Chunk::new(Some(8), 4, 36),
-                Chunk::new(Some(9), 2, 40),
-            ]
+            disk.files[..3],
+            [Span::new(0, 0, 2), Span::new(1, 5, 3), Span::new(2, 11, 1)]
+        );
+        assert_eq!(disk.free[..3], [(2, 3), (8, 3), (12, 3)]);
+        assert_eq!(disk.size, 42);
+    }
+
+    #[test]
+    fn span_checksum() {
+        assert_eq!(Span::new(3, 4, 3).checksum(), 3 * (4 + 5 + 6));
+        assert_eq!(Span::new(3, 4, 0).checksum(), 0);
+    }
+
+    #[test]
+    fn compact_blocks_layout() {
+        let expected = to_blocks("0099811188827773336446555566..............");
+
+        assert_eq!(parse(INPUT).compact(&BlockWise).disk.blocks(), expected);
+    }
+
+    #[test]
+    fn compact_files_layout() {
+        let expected = to_blocks("00992111777.44.333....5555.6666.....8888..");
+
+        assert_eq!(parse(INPUT).compact(&FirstFit).disk.blocks(), expected);
+    }
+
+    #[test]
+    fn compaction_stats() {
+        let disk = parse(INPUT);
+
+        let compaction = disk.compact(&BlockWise);
+        assert_eq!((compaction.moves, compaction.blocks_moved), (7, 12));
+
+        let compaction = disk.compact(&FirstFit);
+        assert_eq!((compaction.moves, compaction.blocks_moved), (4, 8));
+        assert_eq!(compaction.checksum(), 2858);
+    }
+
+    #[test]
+    fn compare_strategies() {
+        let disk = parse("132111");
+        assert_eq!(disk.to_string(), "0...11.2.");
+
+        let first_fit = disk.compact(&FirstFit);
+        assert_eq!(first_fit.disk.to_string(), "0211.....");
+        assert_eq!((first_fit.moves, first_fit.blocks_moved), (2, 3));
+
+        let best_fit = disk.compact(&BestFit);
+        assert_eq!(best_fit.disk.to_string(), "011...2..");
+        assert_eq!((best_fit.moves, best_fit.blocks_moved), (2, 3));
+
+        let towards_end = disk.compact(&TowardsEnd);
+        assert_eq!(towards_end.disk.to_string(), "....11.20");
+        assert_eq!((towards_end.moves, towards_end.blocks_moved), (1, 1));
+
+        assert_eq!(first_fit.checksum(), 2 + 2 + 3);
+        assert_eq!(best_fit.checksum(), 1 + 2 + 6 * 2);
+        assert_eq!(towards_end.checksum(), 4 + 5 + 7 * 2);
+    }
+
+    #[test]
+    fn render_layout() {
+        assert_eq!(parse("12345").to_string(), "0..111....22222");
+        assert_eq!(
+            parse("12345").compact(&BlockWise).disk.to_string(),
+            "022111222......"
         );
     }
 
+    #[test]
+    fn encode_round_trip() {
+        assert_eq!(parse(INPUT).encode(), Ok(INPUT.to_string()));
+        assert_eq!(parse("12345").encode(), Ok("12345".to_string()));
+        assert_eq!(parse("1234").encode(), Ok("1234".to_string()));
+    }
+
+    #[test]
+    fn encode_compacted_files() {
+        let disk = parse("132");
+        assert_eq!(disk.to_string(), "0...11");
+        assert_eq!(disk.compact(&FirstFit).disk.to_string(), "011...");
+        assert_eq!(
+            disk.compact(&FirstFit).disk.encode(),
+            Ok("1023".to_string())
+        );
+    }
+
+    #[test]
+    fn encode_unordered_layout() {
+        assert_eq!(
+            parse(INPUT).compact(&FirstFit).disk.encode(),
+            Err("file 9 found where file 1 expected".to_string())
+        );
+    }
+
+    #[test]
+    fn encode_empty_files() {
+        assert_eq!(parse("1901").encode(), Ok("1901".to_string()));
+        assert_eq!(parse("10021").encode(), Ok("10021".to_string()));
+        assert_eq!(parse("10021").to_string(), "0..2");
+        assert_eq!(parse("10021").free, vec![(1, 2)]);
+    }
+
+    #[test]
+    fn compact_around_empty_files() {
+        let disk = parse("10021");
+
+        let compaction = disk.compact(&BlockWise);
+        assert_eq!(compaction.disk.to_string(), "02..");
+        assert_eq!((compaction.moves, compaction.blocks_moved), (1, 1));
+
+        let compaction = disk.compact(&FirstFit);
+        assert_eq!(compaction.disk.to_string(), "02..");
+        assert_eq!((compaction.moves, compaction.blocks_moved), (1, 1));
+        assert_eq!(compaction.disk.files.len(), 3);
+        assert_eq!(compaction.disk.encode(), Ok("100012".to_string()));
+
+        // file 1 ends up inside file 2 once it moves over it
+        let compaction = parse("11012").compact(&FirstFit);
+        assert_eq!(compaction.disk.to_string(), "022..");
+        assert_eq!(compaction.disk.free, vec![(3, 2)]);
+        assert_eq!(compaction.checksum(), 2 * (1 + 2));
+        assert_eq!(
+            compaction.disk.encode(),
+            Err("file 2 found where file 1 expected".to_string())
+        );
+    }
+
+    #[test]
+    fn fragmentation_stats() {
+        let stats = parse(INPUT).fragmentation();
+        assert_eq!(stats.histogram, vec![0, 5, 0, 3]);
+        assert_eq!(stats.free, 14);
+        assert_eq!(stats.largest_gap, Some((2, 3)));
+
+        let stats = parse(INPUT).compact(&FirstFit).disk.fragmentation();
+        assert_eq!(stats.free, 14);
+        assert_eq!(stats.largest_gap, Some((31, 5)));
+    }
+
     #[test]
     fn part1_example() {
         assert_eq!(part1(&parse(INPUT)), 1928);
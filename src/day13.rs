@@ -1,79 +1,359 @@
 type Point = (i64, i64);
-use std::u64;
 
-use itertools::Itertools;
-use memoize::memoize;
 use regex::Regex;
 
-#[derive(Debug, PartialEq, Clone, Copy, Eq, Hash)]
+#[derive(Debug, PartialEq, Clone)]
+pub struct ClawRules {
+    costs: Vec<u64>,
+    max_presses: Vec<Option<u64>>,
+    prize_offset: i64,
+}
+
+impl Default for ClawRules {
+    fn default() -> Self {
+        Self::new(&[3, 1], &[], 0)
+    }
+}
+
+impl ClawRules {
+    pub fn new(costs: &[u64], max_presses: &[Option<u64>], prize_offset: i64) -> Self {
+        Self {
+            costs: costs.to_vec(),
+            max_presses: max_presses.to_vec(),
+            prize_offset,
+        }
+    }
+
+    fn cost(&self, button: usize) -> i128 {
+        // buttons without an explicit cost take one token per press
+        self.costs.get(button).copied().unwrap_or(1) as i128
+    }
+
+    fn max_presses(&self, button: usize) -> Option<i128> {
+        self.max_presses
+            .get(button)
+            .copied()
+            .flatten()
+            .map(|x| x as i128)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub struct Machine {
-    a: Point,
-    b: Point,
+    buttons: Vec<Point>,
     prize: Point,
 }
 
 impl Machine {
-    pub fn tokens(&self) -> Option<u64> {
-        move_claw(*self, (0, 0), 0, (100, 100))
-    }
-
-    pub fn tokens_part2(&self) -> Option<u64> {
-        let ax = self.a.0;
-        let ay = self.a.1;
-        let bx = self.b.0;
-        let by = self.b.1;
-        let px = self.prize.0;
-        let py = self.prize.1;
-
-        // a*Ax = Px - b*Bx;
-        // a*Ay = Py - b*By;
-        if (py * ax - ay * px) % (by * ax - ay * bx) == 0 {
-            let tokens_b = (py * ax - ay * px) / (by * ax - ay * bx);
-            if (px - tokens_b * bx) % ax == 0 {
-                let tokens_a = (px - tokens_b * bx) / ax;
-                return Some((tokens_a * 3 + tokens_b) as u64);
-            }
+    pub fn new(buttons: &[Point], prize: Point) -> Self {
+        Self {
+            buttons: buttons.to_vec(),
+            prize,
         }
+    }
+
+    pub fn solve(&self, rules: &ClawRules) -> Option<Solution> {
+        let offset = rules.prize_offset as i128;
+        let prize = (self.prize.0 as i128 + offset, self.prize.1 as i128 + offset);
+        let buttons: Vec<(i128, i128)> = self
+            .buttons
+            .iter()
+            .map(|&(x, y)| (x as i128, y as i128))
+            .collect();
+
+        let presses = match buttons.len() {
+            0 => (prize == (0, 0)).then(Vec::new)?,
+            1 => {
+                let (a, _) = solve_pair(buttons[0], (0, 0), prize, rules, (0, 1))?;
+                vec![a]
+            }
+            2 => {
+                let (a, b) = solve_pair(buttons[0], buttons[1], prize, rules, (0, 1))?;
+                vec![a, b]
+            }
+            _ => solve_many(&buttons, prize, rules)?,
+        };
 
-        None
+        let cost = presses
+            .iter()
+            .enumerate()
+            .map(|(i, &n)| rules.cost(i) * n)
+            .sum::<i128>();
+
+        Some(Solution {
+            presses: presses.into_iter().map(|n| n as u64).collect(),
+            cost: cost as u64,
+        })
     }
 }
 
-#[memoize]
-fn move_claw(machine: Machine, position: Point, tokens: u64, remaining: (u64, u64)) -> Option<u64> {
-    if position == machine.prize {
-        return Some(tokens);
-    }
-    if position.0 > machine.prize.0 || position.1 > machine.prize.1 {
-        return None;
-    }
+#[derive(Debug, PartialEq, Clone)]
+pub struct Solution {
+    presses: Vec<u64>,
+    cost: u64,
+}
+
+fn solve_pair(
+    (ax, ay): (i128, i128),
+    (bx, by): (i128, i128),
+    (px, py): (i128, i128),
+    rules: &ClawRules,
+    (i, j): (usize, usize),
+) -> Option<(i128, i128)> {
+    let (max_a, max_b) = (rules.max_presses(i), rules.max_presses(j));
 
-    let res_a = if remaining.0 > 0 {
-        move_claw(
-            machine,
-            (position.0 + machine.a.0, position.1 + machine.a.1),
-            tokens + 3,
-            (remaining.0 - 1, remaining.1),
-        )
+    let det = ax * by - ay * bx;
+    let (a, b) = if det != 0 {
+        // Cramer's rule; only exact, non-negative solutions are valid
+        let a = px * by - py * bx;
+        let b = ax * py - ay * px;
+        if a % det != 0 || b % det != 0 {
+            return None;
+        }
+        (a / det, b / det)
     } else {
-        None
+        // colinear buttons: the prize has to lie on the same line, and
+        // then it's a single equation along whichever axis isn't empty
+        if ax * py - ay * px != 0 || bx * py - by * px != 0 {
+            return None;
+        }
+        let line = if ax != 0 || bx != 0 || px != 0 {
+            (ax, bx, px)
+        } else {
+            (ay, by, py)
+        };
+        solve_line(line, (rules.cost(i), rules.cost(j)), (max_a, max_b))?
     };
 
-    let res_b = if remaining.1 > 0 {
-        move_claw(
-            machine,
-            (position.0 + machine.b.0, position.1 + machine.b.1),
-            tokens + 1,
-            (remaining.0, remaining.1 - 1),
-        )
-    } else {
-        None
+    let within =
+        |presses: i128, max: Option<i128>| presses >= 0 && max.is_none_or(|m| presses <= m);
+    (within(a, max_a) && within(b, max_b)).then_some((a, b))
+}
+
+fn solve_many(
+    buttons: &[(i128, i128)],
+    prize: (i128, i128),
+    rules: &ClawRules,
+) -> Option<Vec<i128>> {
+    // two independent buttons are solved in closed form, and so is the last
+    // of the others; any button before that is searched, bounded by how far
+    // it can go before overshooting the prize (buttons are expected to move
+    // forwards) and by the rules' cap if there is one
+    let n = buttons.len();
+    let (i, j) = (0..n)
+        .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+        .find(|&(i, j)| {
+            let ((ax, ay), (bx, by)) = (buttons[i], buttons[j]);
+            ax * by - ay * bx != 0
+        })
+        .unwrap_or((n - 2, n - 1));
+    let extra: Vec<usize> = (0..n).filter(|&k| k != i && k != j).collect();
+
+    let mut search = Search {
+        buttons,
+        rules,
+        pair: (i, j),
+        extra: &extra,
+        presses: vec![0; n],
+        best: None,
     };
+    search.run(0, prize, 0);
 
-    if let Some(tokens_a) = res_a {
-        Some(tokens_a.min(res_b.unwrap_or(u64::MAX)))
+    search.best.map(|(_, presses)| presses)
+}
+
+struct Search<'a> {
+    buttons: &'a [(i128, i128)],
+    rules: &'a ClawRules,
+    pair: (usize, usize),
+    extra: &'a [usize],
+    presses: Vec<i128>,
+    best: Option<(i128, Vec<i128>)>,
+}
+
+impl Search<'_> {
+    fn run(&mut self, depth: usize, remaining: (i128, i128), cost: i128) {
+        if self.best.as_ref().is_some_and(|(best, _)| cost >= *best) {
+            return;
+        }
+
+        if depth + 1 == self.extra.len() && self.pair_det() != 0 {
+            self.finish(self.extra[depth], remaining, cost);
+            return;
+        }
+
+        if depth == self.extra.len() {
+            let (i, j) = self.pair;
+            let pair = solve_pair(
+                self.buttons[i],
+                self.buttons[j],
+                remaining,
+                self.rules,
+                (i, j),
+            );
+            if let Some((a, b)) = pair {
+                let total = cost + self.rules.cost(i) * a + self.rules.cost(j) * b;
+                if self.best.as_ref().is_none_or(|(best, _)| total < *best) {
+                    self.presses[i] = a;
+                    self.presses[j] = b;
+                    self.best = Some((total, self.presses.clone()));
+                }
+            }
+            return;
+        }
+
+        let k = self.extra[depth];
+        let (x, y) = self.buttons[k];
+        let reach = [(x, remaining.0), (y, remaining.1)]
+            .into_iter()
+            .filter(|&(step, _)| step > 0)
+            .map(|(step, left)| left.div_euclid(step))
+            .min()
+            .unwrap_or(0);
+        let limit = self
+            .rules
+            .max_presses(k)
+            .map_or(reach, |max| reach.min(max));
+
+        for presses in 0..=limit {
+            self.presses[k] = presses;
+            let remaining = (remaining.0 - presses * x, remaining.1 - presses * y);
+            self.run(depth + 1, remaining, cost + self.rules.cost(k) * presses);
+        }
+        self.presses[k] = 0;
+    }
+
+    fn pair_det(&self) -> i128 {
+        let ((ax, ay), (bx, by)) = (self.buttons[self.pair.0], self.buttons[self.pair.1]);
+        ax * by - ay * bx
+    }
+
+    fn finish(&mut self, k: usize, (px, py): (i128, i128), cost: i128) {
+        // with n presses of button k, Cramer's rule gives the pair as
+        // a = (na - n * ca) / det and b = (nb - n * cb) / det, so whether
+        // they are whole numbers only depends on n mod |det|; within each
+        // of those residues the cost is linear in n
+        let (i, j) = self.pair;
+        let ((ax, ay), (bx, by)) = (self.buttons[i], self.buttons[j]);
+        let (x, y) = self.buttons[k];
+        let det = self.pair_det();
+        let period = det.abs();
+
+        let (na, ca) = (px * by - py * bx, x * by - y * bx);
+        let (nb, cb) = (ax * py - ay * px, ax * y - ay * x);
+        // how much a and b change when n grows by a whole period
+        let (da, db) = (-ca * period / det, -cb * period / det);
+        let (cost_a, cost_b, cost_k) = (self.rules.cost(i), self.rules.cost(j), self.rules.cost(k));
+
+        for r in 0..period {
+            if (na - r * ca) % det != 0 || (nb - r * cb) % det != 0 {
+                continue;
+            }
+            let (a0, b0) = ((na - r * ca) / det, (nb - r * cb) / det);
+
+            // n = r + t * period for some t >= 0
+            let mut constraints = vec![(0, 1), (a0, da), (b0, db)];
+            for (max, c, d) in [
+                (self.rules.max_presses(k), r, period),
+                (self.rules.max_presses(i), a0, da),
+                (self.rules.max_presses(j), b0, db),
+            ] {
+                if let Some(max) = max {
+                    constraints.push((max - c, -d));
+                }
+            }
+
+            let slope = cost_k * period + cost_a * da + cost_b * db;
+            let Some(t) = cheapest_step(&constraints, slope) else {
+                continue;
+            };
+
+            let (n, a, b) = (r + t * period, a0 + t * da, b0 + t * db);
+            let total = cost + cost_k * n + cost_a * a + cost_b * b;
+            if self.best.as_ref().is_none_or(|(best, _)| total < *best) {
+                self.presses[k] = n;
+                self.presses[i] = a;
+                self.presses[j] = b;
+                self.best = Some((total, self.presses.clone()));
+                self.presses[k] = 0;
+            }
+        }
+    }
+}
+
+fn ext_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        (a.abs(), a.signum(), 0)
     } else {
-        res_b
+        let (g, x, y) = ext_gcd(b, a.rem_euclid(b));
+        (g, y, x - a.div_euclid(b) * y)
+    }
+}
+
+fn solve_line(
+    (u, v, w): (i128, i128, i128),
+    (cost_u, cost_v): (i128, i128),
+    (max_a, max_b): (Option<i128>, Option<i128>),
+) -> Option<(i128, i128)> {
+    // cheapest non-negative integer solution of a * u + b * v = w
+    if u == 0 && v == 0 {
+        return (w == 0).then_some((0, 0));
+    }
+
+    let (g, x, y) = ext_gcd(u, v);
+    if w % g != 0 {
+        return None;
+    }
+
+    // every solution is (a0 + k * da, b0 + k * db) for some integer k
+    let (a0, b0) = (x * (w / g), y * (w / g));
+    let (da, db) = (v / g, -u / g);
+
+    // each constraint is c + k * d >= 0
+    let mut constraints = vec![(a0, da), (b0, db)];
+    if let Some(max) = max_a {
+        constraints.push((max - a0, -da));
+    }
+    if let Some(max) = max_b {
+        constraints.push((max - b0, -db));
+    }
+
+    // the cost is linear in k, so the cheapest solution sits at one end
+    let k = cheapest_step(&constraints, cost_u * da + cost_v * db)?;
+
+    Some((a0 + k * da, b0 + k * db))
+}
+
+fn cheapest_step(constraints: &[(i128, i128)], slope: i128) -> Option<i128> {
+    // the k meeting every constraint c + k * d >= 0 that minimises k * slope
+    let mut lo: Option<i128> = None;
+    let mut hi: Option<i128> = None;
+    for &(c, d) in constraints {
+        match d.signum() {
+            1 => {
+                let bound = -(c.div_euclid(d));
+                lo = Some(lo.map_or(bound, |lo| lo.max(bound)));
+            }
+            -1 => {
+                let bound = c.div_euclid(-d);
+                hi = Some(hi.map_or(bound, |hi| hi.min(bound)));
+            }
+            _ if c < 0 => return None,
+            _ => {}
+        }
+    }
+
+    if let (Some(lo), Some(hi)) = (lo, hi) {
+        if lo > hi {
+            return None;
+        }
+    }
+
+    match (slope.signum(), lo, hi) {
+        (1, Some(lo), _) | (0, Some(lo), _) => Some(lo),
+        (-1, _, Some(hi)) | (0, None, Some(hi)) => Some(hi),
+        (0, None, None) => Some(0),
+        _ => None,
     }
 }
 
@@ -81,48 +361,44 @@ use aoc_runner_derive::{aoc, aoc_generator};
 #[aoc_generator(day13)]
 fn parse(input: &str) -> Vec<Machine> {
     let re = Regex::new(r"\d+").unwrap();
+    let mut machines = vec![];
+    let mut buttons = vec![];
 
-    input
-        .lines()
-        .filter(|l| !l.trim().is_empty())
-        .chunks(3)
-        .into_iter()
-        .map(|chunk| {
-            let rows: Vec<Point> = chunk
-                .map(|line| {
-                    let numbers = re
-                        .find_iter(line)
-                        .map(|matched| matched.as_str().parse::<i64>().unwrap())
-                        .collect::<Vec<i64>>();
-
-                    (numbers[0], numbers[1])
-                })
-                .collect();
-            Machine {
-                a: rows[0],
-                b: rows[1],
-                prize: rows[2],
-            }
-        })
-        .collect()
+    for line in input.lines().filter(|l| !l.trim().is_empty()) {
+        let numbers = re
+            .find_iter(line)
+            .map(|matched| matched.as_str().parse::<i64>().unwrap())
+            .collect::<Vec<i64>>();
+        let point = (numbers[0], numbers[1]);
+
+        if line.starts_with("Prize") {
+            machines.push(Machine::new(&buttons, point));
+            buttons.clear();
+        } else {
+            buttons.push(point);
+        }
+    }
+
+    machines
 }
 
 #[aoc(day13, part1)]
 fn part1(input: &[Machine]) -> u64 {
-    input.into_iter().filter_map(|m| m.tokens()).sum()
+    let rules = ClawRules::new(&[3, 1], &[Some(100), Some(100)], 0);
+    total_cost(input, &rules)
 }
 
 #[aoc(day13, part2)]
 fn part2(input: &[Machine]) -> u64 {
-    let inc = 10_000_000_000_000;
-    input
-        .into_iter()
-        .map(|m| Machine {
-            a: m.a,
-            b: m.b,
-            prize: (m.prize.0 + inc, m.prize.1 + inc),
-        })
-        .filter_map(|m| m.tokens_part2())
+    let rules = ClawRules::new(&[3, 1], &[], 10_000_000_000_000);
+    total_cost(input, &rules)
+}
+
+fn total_cost(machines: &[Machine], rules: &ClawRules) -> u64 {
+    machines
+        .iter()
+        .filter_map(|m| m.solve(rules))
+        .map(|solution| solution.cost)
         .sum()
 }
 
@@ -149,39 +425,41 @@ Prize: X=18641, Y=10279";
     #[test]
     fn parse_input() {
         let expected = vec![
-            Machine {
-                a: (94, 34),
-                b: (22, 67),
-                prize: (8400, 5400),
-            },
-            Machine {
-                a: (26, 66),
-                b: (67, 21),
-                prize: (12748, 12176),
-            },
-            Machine {
-                a: (17, 86),
-                b: (84, 37),
-                prize: (7870, 6450),
-            },
-            Machine {
-                a: (69, 23),
-                b: (27, 71),
-                prize: (18641, 10279),
-            },
+            Machine::new(&[(94, 34), (22, 67)], (8400, 5400)),
+            Machine::new(&[(26, 66), (67, 21)], (12748, 12176)),
+            Machine::new(&[(17, 86), (84, 37)], (7870, 6450)),
+            Machine::new(&[(69, 23), (27, 71)], (18641, 10279)),
         ];
 
         assert_eq!(parse(INPUT), expected);
     }
 
+    #[test]
+    fn parse_machines_with_any_number_of_buttons() {
+        let input = r"Button A: X+2, Y+0
+Button B: X+0, Y+2
+Button C: X+1, Y+1
+Prize: X=4, Y=4
+
+Button A: X+3, Y+3
+Prize: X=9, Y=9";
+
+        assert_eq!(
+            parse(input),
+            vec![
+                Machine::new(&[(2, 0), (0, 2), (1, 1)], (4, 4)),
+                Machine::new(&[(3, 3)], (9, 9)),
+            ]
+        );
+    }
+
     #[test]
     fn tokens_one_movement() {
-        let machine = Machine {
-            a: (1, 1),
-            b: (2, 1),
-            prize: (2, 1),
-        };
-        assert_eq!(machine.tokens(), Some(1));
+        let machine = Machine::new(&[(1, 1), (2, 1)], (2, 1));
+        assert_eq!(
+            machine.solve(&ClawRules::default()).map(|s| s.cost),
+            Some(1)
+        );
     }
 
     #[test]
@@ -191,12 +469,215 @@ Prize: X=18641, Y=10279";
 
     #[test]
     fn part2() {
-        let machine = Machine {
-            a: (94, 34),
-            b: (22, 67),
-            prize: (8400, 5400),
-        };
+        let machine = Machine::new(&[(94, 34), (22, 67)], (8400, 5400));
+
+        assert_eq!(
+            machine.solve(&ClawRules::default()),
+            Some(Solution {
+                presses: vec![80, 40],
+                cost: 280
+            })
+        );
+    }
+
+    #[test]
+    fn solve_without_solution() {
+        let machine = Machine::new(&[(26, 66), (67, 21)], (12748, 12176));
+
+        assert_eq!(machine.solve(&ClawRules::default()), None);
+    }
+
+    #[test]
+    fn solve_rejects_negative_presses() {
+        // the only exact solution is a = 2, b = -1
+        let machine = Machine::new(&[(2, 1), (1, 1)], (3, 1));
+
+        assert_eq!(machine.solve(&ClawRules::default()), None);
+    }
+
+    #[test]
+    fn solve_colinear_buttons() {
+        // A moves (2, 2) for 3 tokens and B moves (3, 3) for 1 token, so the
+        // cheapest way to reach (12, 12) is 4 presses of B
+        let machine = Machine::new(&[(2, 2), (3, 3)], (12, 12));
+        assert_eq!(
+            machine.solve(&ClawRules::default()),
+            Some(Solution {
+                presses: vec![0, 4],
+                cost: 4
+            })
+        );
+
+        // 7 = 2 * 2 + 1 * 3 is the only non-negative combination
+        let machine = Machine::new(&[(2, 4), (3, 6)], (7, 14));
+        assert_eq!(
+            machine.solve(&ClawRules::default()),
+            Some(Solution {
+                presses: vec![2, 1],
+                cost: 7
+            })
+        );
+    }
+
+    #[test]
+    fn solve_colinear_buttons_off_line() {
+        let machine = Machine::new(&[(2, 2), (3, 3)], (12, 13));
+        assert_eq!(machine.solve(&ClawRules::default()), None);
+
+        let machine = Machine::new(&[(2, 2), (4, 4)], (7, 7));
+        assert_eq!(machine.solve(&ClawRules::default()), None);
+    }
+
+    #[test]
+    fn solve_with_press_limits() {
+        let machine = Machine::new(&[(94, 34), (22, 67)], (8400, 5400));
+
+        let rules = ClawRules::new(&[3, 1], &[Some(80), Some(40)], 0);
+        assert_eq!(machine.solve(&rules).map(|s| s.cost), Some(280));
+
+        let rules = ClawRules::new(&[3, 1], &[Some(79)], 0);
+        assert_eq!(machine.solve(&rules), None);
+    }
+
+    #[test]
+    fn solve_colinear_with_limits_and_costs() {
+        let machine = Machine::new(&[(2, 2), (3, 3)], (12, 12));
+
+        // B alone would need 4 presses, so with a cap of 2 A has to help
+        let rules = ClawRules::new(&[3, 1], &[None, Some(2)], 0);
+        assert_eq!(
+            machine.solve(&rules),
+            Some(Solution {
+                presses: vec![3, 2],
+                cost: 11
+            })
+        );
+
+        // with A cheaper, 6 presses of A beat any mix
+        let rules = ClawRules::new(&[1, 5], &[], 0);
+        assert_eq!(
+            machine.solve(&rules),
+            Some(Solution {
+                presses: vec![6, 0],
+                cost: 6
+            })
+        );
+
+        let rules = ClawRules::new(&[3, 1], &[Some(1), Some(2)], 0);
+        assert_eq!(machine.solve(&rules), None);
+    }
+
+    #[test]
+    fn solve_vertical_and_empty_buttons() {
+        let machine = Machine::new(&[(0, 5), (0, 0)], (0, 15));
+        assert_eq!(
+            machine.solve(&ClawRules::default()).map(|s| s.cost),
+            Some(9)
+        );
+
+        let machine = Machine::new(&[(0, 0), (0, 0)], (0, 0));
+        assert_eq!(
+            machine.solve(&ClawRules::default()).map(|s| s.cost),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn solve_huge_prize() {
+        let machine = Machine::new(&[(26, 66), (67, 21)], (12748, 12176));
+        let rules = ClawRules::new(&[3, 1], &[], 10_000_000_000_000);
+
+        assert_eq!(
+            machine.solve(&rules),
+            Some(Solution {
+                presses: vec![118679050709, 103199174542],
+                cost: 459236326669
+            })
+        );
+    }
+
+    #[test]
+    fn solve_three_buttons() {
+        let machine = Machine::new(&[(2, 0), (0, 2), (1, 1)], (4, 4));
+
+        // the third button defaults to one token per press
+        assert_eq!(
+            machine.solve(&ClawRules::default()),
+            Some(Solution {
+                presses: vec![0, 0, 4],
+                cost: 4
+            })
+        );
+
+        let rules = ClawRules::new(&[3, 1, 10], &[], 0);
+        assert_eq!(
+            machine.solve(&rules),
+            Some(Solution {
+                presses: vec![2, 2, 0],
+                cost: 8
+            })
+        );
+
+        let rules = ClawRules::new(&[3, 1, 1], &[None, None, Some(2)], 0);
+        assert_eq!(
+            machine.solve(&rules),
+            Some(Solution {
+                presses: vec![1, 1, 2],
+                cost: 6
+            })
+        );
+    }
+
+    #[test]
+    fn solve_single_button() {
+        let machine = Machine::new(&[(3, 3)], (9, 9));
+        assert_eq!(
+            machine.solve(&ClawRules::default()),
+            Some(Solution {
+                presses: vec![3],
+                cost: 9
+            })
+        );
+
+        let machine = Machine::new(&[(3, 3)], (9, 10));
+        assert_eq!(machine.solve(&ClawRules::default()), None);
+    }
+
+    #[test]
+    fn solve_three_buttons_huge_prize() {
+        let machine = Machine::new(&[(94, 34), (22, 67), (7, 5)], (8400, 5400));
+        let offset = 10_000_000_000_000;
+        let solution = machine
+            .solve(&ClawRules::new(&[3, 1, 2], &[], offset))
+            .unwrap();
+
+        let reached = machine
+            .buttons
+            .iter()
+            .zip(solution.presses.iter())
+            .fold((0, 0), |(x, y), (&(dx, dy), &n)| {
+                (x + dx * n as i64, y + dy * n as i64)
+            });
+        assert_eq!(reached, (8400 + offset, 5400 + offset));
+        assert_eq!(solution.cost, 351351359570);
+
+        // the pair only lands on whole presses when the third button is
+        // pressed 4500 times modulo the determinant of the first two
+        let rules = ClawRules::new(&[3, 1, 2], &[None, None, Some(4499)], offset);
+        assert_eq!(machine.solve(&rules), None);
+        let rules = ClawRules::new(&[3, 1, 2], &[None, None, Some(4500)], offset);
+        assert_eq!(machine.solve(&rules), Some(solution));
+    }
+
+    #[test]
+    fn solve_many_buttons_far_prize() {
+        // the cheapest way is a million presses of the third button alone
+        let machine = Machine::new(&[(1, 0), (0, 1), (1, 1), (2, 2)], (1_000_000, 1_000_000));
+        let solution = machine
+            .solve(&ClawRules::new(&[3, 3, 1, 5], &[], 0))
+            .unwrap();
 
-        assert_eq!(machine.tokens_part2(), Some(280));
+        assert_eq!(solution.cost, 1_000_000);
+        assert_eq!(solution.presses, vec![0, 0, 1_000_000, 0]);
     }
 }
@@ -2,9 +2,13 @@ use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
 use regex::Regex;
 
+mod render;
+
 type Point = (i32, i32);
 type Size = (i32, i32);
 
+const ROOM_SIZE: Size = (101, 103);
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct Robot {
     position: Point,
@@ -12,19 +16,35 @@ pub struct Robot {
 }
 
 impl Robot {
+    #[allow(dead_code)]
     fn tick(&mut self, bounds: (i32, i32)) {
         self.position.0 = (self.position.0 + self.velocity.0).rem_euclid(bounds.0);
         self.position.1 = (self.position.1 + self.velocity.1).rem_euclid(bounds.1);
     }
 
+    pub fn position_at(&self, tick: u64, bounds: Size) -> Point {
+        let axis = |position: i32, velocity: i32, size: i32| {
+            let size = size as i64;
+            let steps = (tick % size as u64) as i64;
+            (position as i64 + velocity as i64 * steps).rem_euclid(size) as i32
+        };
+
+        (
+            axis(self.position.0, self.velocity.0, bounds.0),
+            axis(self.position.1, self.velocity.1, bounds.1),
+        )
+    }
+
     fn quadrant(&self, bounds: (i32, i32)) -> Option<Quadrant> {
-        let half_width = bounds.0 / 2;
-        let half_height = bounds.1 / 2;
+        // odd-sized rooms have a middle row / column that belongs to no
+        // quadrant, while even-sized rooms split cleanly in two halves
+        let (left, right) = (bounds.0 / 2, (bounds.0 + 1) / 2);
+        let (top, bottom) = (bounds.1 / 2, (bounds.1 + 1) / 2);
         match self.position {
-            (x, y) if x < half_width && y < half_height => Some(Quadrant::TopLeft),
-            (x, y) if x > half_width && y < half_height => Some(Quadrant::TopRight),
-            (x, y) if x < half_width && y > half_height => Some(Quadrant::BottomLeft),
-            (x, y) if x > half_width && y > half_height => Some(Quadrant::BottomRight),
+            (x, y) if x < left && y < top => Some(Quadrant::TopLeft),
+            (x, y) if x >= right && y < top => Some(Quadrant::TopRight),
+            (x, y) if x < left && y >= bottom => Some(Quadrant::BottomLeft),
+            (x, y) if x >= right && y >= bottom => Some(Quadrant::BottomRight),
             _ => None,
         }
     }
@@ -52,54 +72,148 @@ impl Map {
         }
     }
 
+    #[allow(dead_code)]
     pub fn run(&mut self, ticks: usize) -> Vec<Robot> {
-        for _ in 0..ticks {
-            for robot in self.robots.iter_mut() {
-                robot.tick(self.bounds);
+        *self = self.snapshot_at(ticks as u64);
+        self.robots.clone()
+    }
+
+    pub fn safety_factor(&self) -> u64 {
+        let mut counts = [0; 4];
+        for quadrant in self.robots.iter().filter_map(|r| r.quadrant(self.bounds)) {
+            counts[quadrant as usize] += 1;
+        }
+
+        counts.into_iter().product()
+    }
+
+    pub fn snapshot_at(&self, tick: u64) -> Self {
+        let robots = self
+            .robots
+            .iter()
+            .map(|r| Robot {
+                position: r.position_at(tick, self.bounds),
+                velocity: r.velocity,
+            })
+            .collect();
+
+        Self {
+            robots,
+            bounds: self.bounds,
+        }
+    }
+
+    pub fn easter_egg_tick(&self) -> Option<u64> {
+        // x positions repeat every `width` ticks and y positions every `height`
+        // ticks, so find the tightest tick for each axis separately and then
+        // combine them with the chinese remainder theorem; when the sizes
+        // share a factor the two ticks may never line up
+        let (width, height) = self.bounds;
+        let mut best_x = (f64::MAX, 0);
+        let mut best_y = (f64::MAX, 0);
+
+        for tick in 0..width.max(height) {
+            let (var_x, var_y) = self.snapshot_at(tick as u64).variance();
+            if tick < width && var_x < best_x.0 {
+                best_x = (var_x, tick);
+            }
+            if tick < height && var_y < best_y.0 {
+                best_y = (var_y, tick);
             }
         }
 
-        self.robots.clone()
+        crt((best_x.1, width), (best_y.1, height))
+    }
+
+    fn variance(&self) -> (f64, f64) {
+        let n = self.robots.len() as f64;
+        let (sum_x, sum_y) = self.robots.iter().fold((0.0, 0.0), |(x, y), r| {
+            (x + r.position.0 as f64, y + r.position.1 as f64)
+        });
+        let (mean_x, mean_y) = (sum_x / n, sum_y / n);
+
+        self.robots.iter().fold((0.0, 0.0), |(x, y), r| {
+            let dx = r.position.0 as f64 - mean_x;
+            let dy = r.position.1 as f64 - mean_y;
+            (x + dx * dx / n, y + dy * dy / n)
+        })
+    }
+}
+
+impl std::fmt::Display for Map {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let counts = self.robots.iter().counts_by(|r| r.position);
+        for y in 0..self.bounds.1 {
+            let row: String = (0..self.bounds.0)
+                .map(|x| match counts.get(&(x, y)) {
+                    Some(&n) if n < 10 => char::from_digit(n as u32, 10).unwrap(),
+                    Some(_) => '*',
+                    None => '.',
+                })
+                .collect();
+            writeln!(f, "{}", row)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn crt((a, n): (i32, i32), (b, m): (i32, i32)) -> Option<u64> {
+    // the first tick `t` with `t % n == a` and `t % m == b`; the moduli need
+    // not be coprime, but then `a` and `b` have to agree modulo their gcd
+    let (a, n, b, m) = (a as u64, n as u64, b as u64, m as u64);
+    let g = gcd(n, m);
+    if a % g != b % g {
+        return None;
+    }
+
+    (0..m / g).map(|k| a + k * n).find(|&t| t % m == b)
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
     }
 }
 
 #[aoc_generator(day14)]
-fn parse(input: &str) -> Vec<Robot> {
+fn parse(input: &str) -> Map {
     let re = Regex::new(r"-?\d+").unwrap();
+    let mut bounds = ROOM_SIZE;
+    let mut robots = vec![];
 
-    input
-        .lines()
-        .map(|line| {
-            let nums: Vec<i32> = re
-                .find_iter(line)
-                .map(|x| x.as_str().parse().unwrap())
-                .collect();
-            Robot {
+    for line in input.lines().filter(|l| !l.trim().is_empty()) {
+        let nums: Vec<i32> = re
+            .find_iter(line)
+            .map(|x| x.as_str().parse().unwrap())
+            .collect();
+
+        // an optional `size=W,H` line overrides the default room size
+        if line.starts_with("size=") {
+            bounds = (nums[0], nums[1]);
+        } else {
+            robots.push(Robot {
                 position: (nums[0], nums[1]),
                 velocity: (nums[2], nums[3]),
-            }
-        })
-        .collect()
+            });
+        }
+    }
+
+    Map::new(&robots, bounds)
 }
 
 #[aoc(day14, part1)]
-fn part1(input: &[Robot]) -> u64 {
-    let bounds = if cfg!(test) { (11, 7) } else { (101, 103) };
-
-    let mut map = Map::new(input, bounds);
-    let robots = map.run(100);
-    robots
-        .into_iter()
-        .sorted_by_key(|r| r.quadrant(bounds))
-        .chunk_by(|r| r.quadrant(bounds))
-        .into_iter()
-        .filter_map(|(quad, group)| quad.map(|_| group.count()))
-        .fold(1, |acc, x| x * acc) as u64
+fn part1(input: &Map) -> u64 {
+    input.snapshot_at(100).safety_factor()
 }
 
 #[aoc(day14, part2)]
-fn part2(_: &[Robot]) -> u64 {
-    todo!()
+fn part2(input: &Map) -> Result<u64, String> {
+    input
+        .easter_egg_tick()
+        .ok_or("The robots never gather on both axes at once".to_string())
 }
 
 #[cfg(test)]
@@ -124,13 +238,24 @@ p=9,5 v=-3,-3";
         let input = r"p=0,4 v=3,-3";
         assert_eq!(
             parse(input),
-            vec![Robot {
-                position: (0, 4),
-                velocity: (3, -3)
-            }]
+            Map::new(
+                &[Robot {
+                    position: (0, 4),
+                    velocity: (3, -3)
+                }],
+                ROOM_SIZE
+            )
         );
     }
 
+    #[test]
+    fn parse_input_with_size() {
+        let input = r"size=11,7
+p=0,4 v=3,-3";
+        assert_eq!(parse(input).bounds, (11, 7));
+        assert_eq!(parse(input).robots.len(), 1);
+    }
+
     #[test]
     fn robot_tick() {
         let mut robot = Robot {
@@ -143,8 +268,143 @@ p=9,5 v=-3,-3";
         assert_eq!(robot.position, (3, 4));
     }
 
+    #[test]
+    fn robot_position_at_matches_ticking() {
+        let mut robot = Robot {
+            position: (2, 4),
+            velocity: (2, -3),
+        };
+        let start = robot;
+
+        for _ in 0..5 {
+            robot.tick((11, 7));
+        }
+
+        assert_eq!(start.position_at(5, (11, 7)), robot.position);
+        assert_eq!(start.position_at(0, (11, 7)), (2, 4));
+    }
+
+    #[test]
+    fn robot_position_at_huge_tick() {
+        let robot = Robot {
+            position: (2, 4),
+            velocity: (2, -3),
+        };
+
+        // periods are 11 and 7, so 10^12 is equivalent to 1 on x and 1 on y
+        assert_eq!(
+            robot.position_at(1_000_000_000_000, (11, 7)),
+            robot.position_at(1, (11, 7))
+        );
+        assert_eq!(robot.position_at(u64::MAX, (11, 7)), (10, 1));
+    }
+
+    #[test]
+    fn map_snapshot_matches_run() {
+        let mut robots = parse(INPUT).robots;
+        let map = Map::new(&robots, (11, 7));
+        let snapshot = map.snapshot_at(100);
+
+        for _ in 0..100 {
+            robots.iter_mut().for_each(|r| r.tick((11, 7)));
+        }
+
+        assert_eq!(snapshot.robots, robots);
+    }
+
     #[test]
     fn part1_example() {
-        assert_eq!(part1(&parse(INPUT)), 12);
+        assert_eq!(part1(&Map::new(&parse(INPUT).robots, (11, 7))), 12);
+        assert_eq!(part1(&parse(&format!("size=11,7\n{}", INPUT))), 12);
+    }
+
+    #[test]
+    fn part1_example_in_full_room() {
+        assert_eq!(part1(&parse(INPUT)), 21);
+    }
+
+    #[test]
+    fn quadrants_in_even_sized_room() {
+        let robot_at = |position| Robot {
+            position,
+            velocity: (0, 0),
+        };
+
+        assert_eq!(robot_at((4, 2)).quadrant((10, 6)), Some(Quadrant::TopLeft));
+        assert_eq!(robot_at((5, 2)).quadrant((10, 6)), Some(Quadrant::TopRight));
+        assert_eq!(
+            robot_at((4, 3)).quadrant((10, 6)),
+            Some(Quadrant::BottomLeft)
+        );
+        assert_eq!(
+            robot_at((5, 3)).quadrant((10, 6)),
+            Some(Quadrant::BottomRight)
+        );
+        assert_eq!(robot_at((5, 3)).quadrant((11, 7)), None);
+    }
+
+    #[test]
+    fn safety_factor_in_even_sized_room() {
+        let robots: Vec<Robot> = [(0, 0), (1, 1), (9, 0), (0, 5), (9, 5), (5, 3)]
+            .into_iter()
+            .map(|position| Robot {
+                position,
+                velocity: (0, 0),
+            })
+            .collect();
+
+        assert_eq!(Map::new(&robots, (10, 6)).safety_factor(), 4);
+        assert_eq!(Map::new(&robots[..3], (10, 6)).safety_factor(), 0);
+    }
+
+    #[test]
+    fn crt_combines_periods() {
+        assert_eq!(crt((3, 11), (2, 7)), Some(58));
+        assert_eq!(crt((0, 11), (0, 7)), Some(0));
+        assert_eq!(crt((3, 10), (1, 6)), Some(13));
+        assert_eq!(crt((9, 10), (5, 6)), Some(29));
+        assert_eq!(crt((3, 10), (2, 6)), None);
+    }
+
+    #[test]
+    fn easter_egg_tick_finds_gathering() {
+        // every robot ends up at (5, 3) on tick 30
+        let bounds = (11, 7);
+        let robots: Vec<Robot> = [(1, 2), (-3, 1), (4, -2), (2, 3), (-1, -1), (5, 2)]
+            .into_iter()
+            .map(|velocity: Point| Robot {
+                position: (
+                    (5 - velocity.0 * 30).rem_euclid(bounds.0),
+                    (3 - velocity.1 * 30).rem_euclid(bounds.1),
+                ),
+                velocity,
+            })
+            .collect();
+        let map = Map::new(&robots, bounds);
+
+        assert_eq!(map.easter_egg_tick(), Some(30));
+
+        let map = map.snapshot_at(30);
+        assert_eq!(map.to_string().lines().nth(3), Some(".....6....."));
+    }
+
+    #[test]
+    fn easter_egg_tick_in_even_sized_room() {
+        // width and height share a factor of 2, so the ticks repeat every 30
+        let bounds = (10, 6);
+        let robots: Vec<Robot> = [(1, 2), (-3, 1), (4, -2), (2, 3), (-1, -1), (5, 2)]
+            .into_iter()
+            .map(|velocity: Point| Robot {
+                position: (
+                    (5 - velocity.0 * 13).rem_euclid(bounds.0),
+                    (3 - velocity.1 * 13).rem_euclid(bounds.1),
+                ),
+                velocity,
+            })
+            .collect();
+        let map = Map::new(&robots, bounds);
+
+        assert_eq!(map.easter_egg_tick(), Some(13));
+        assert_eq!(part2(&map), Ok(13));
     }
 }
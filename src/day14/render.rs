@@ -0,0 +1,150 @@
+use itertools::Itertools;
+use std::fmt::Write;
+
+use super::Map;
+
+#[allow(dead_code)]
+impl Map {
+    pub fn density(&self) -> Vec<Vec<usize>> {
+        let mut rows = vec![vec![0; self.bounds.0 as usize]; self.bounds.1 as usize];
+        for robot in self.robots.iter() {
+            rows[robot.position.1 as usize][robot.position.0 as usize] += 1;
+        }
+
+        rows
+    }
+
+    pub fn to_pbm(&self) -> String {
+        let mut out = format!("P1\n{} {}\n", self.bounds.0, self.bounds.1);
+        for row in self.density() {
+            let line = row.iter().map(|&n| if n > 0 { "1" } else { "0" }).join(" ");
+            writeln!(out, "{}", line).unwrap();
+        }
+
+        out
+    }
+
+    pub fn to_pgm(&self) -> String {
+        let density = self.density();
+        let max = density.iter().flatten().copied().max().unwrap_or(0).max(1);
+
+        let mut out = format!("P2\n{} {}\n{}\n", self.bounds.0, self.bounds.1, max);
+        for row in density {
+            writeln!(out, "{}", row.iter().join(" ")).unwrap();
+        }
+
+        out
+    }
+
+    pub fn contact_sheet(&self, ticks: &[u64], columns: usize) -> String {
+        // frames are laid out left to right, top to bottom, with a one pixel
+        // white border around each of them
+        let (width, height) = (self.bounds.0 as usize, self.bounds.1 as usize);
+        let columns = columns.clamp(1, ticks.len().max(1));
+        let rows = ticks.len().div_ceil(columns);
+        let sheet_width = columns * (width + 1) + 1;
+        let sheet_height = rows * (height + 1) + 1;
+
+        let frames: Vec<Vec<Vec<usize>>> = ticks
+            .iter()
+            .map(|&t| self.snapshot_at(t).density())
+            .collect();
+        let max = frames
+            .iter()
+            .flatten()
+            .flatten()
+            .copied()
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        let mut pixels = vec![vec![max; sheet_width]; sheet_height];
+        for (i, frame) in frames.iter().enumerate() {
+            let left = (i % columns) * (width + 1) + 1;
+            let top = (i / columns) * (height + 1) + 1;
+            for (y, row) in frame.iter().enumerate() {
+                pixels[top + y][left..left + width].copy_from_slice(row);
+            }
+        }
+
+        let mut out = format!("P2\n{} {}\n{}\n", sheet_width, sheet_height, max);
+        for row in pixels {
+            writeln!(out, "{}", row.iter().join(" ")).unwrap();
+        }
+
+        out
+    }
+
+    pub fn stats_csv(&self, ticks: impl Iterator<Item = u64>) -> String {
+        let mut out = String::from("tick,safety_factor,variance_x,variance_y\n");
+        for tick in ticks {
+            let snapshot = self.snapshot_at(tick);
+            let (var_x, var_y) = snapshot.variance();
+            writeln!(
+                out,
+                "{},{},{:.3},{:.3}",
+                tick,
+                snapshot.safety_factor(),
+                var_x,
+                var_y
+            )
+            .unwrap();
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Robot;
+    use super::*;
+
+    fn map() -> Map {
+        let robots = [((0, 0), (1, 0)), ((0, 0), (0, 1)), ((2, 1), (0, 0))]
+            .into_iter()
+            .map(|(position, velocity)| Robot { position, velocity })
+            .collect::<Vec<Robot>>();
+
+        Map::new(&robots, (3, 2))
+    }
+
+    #[test]
+    fn density_counts_robots() {
+        assert_eq!(map().density(), vec![vec![2, 0, 0], vec![0, 0, 1]]);
+    }
+
+    #[test]
+    fn pbm_export() {
+        assert_eq!(map().to_pbm(), "P1\n3 2\n1 0 0\n0 0 1\n");
+    }
+
+    #[test]
+    fn pgm_export() {
+        assert_eq!(map().to_pgm(), "P2\n3 2\n2\n2 0 0\n0 0 1\n");
+    }
+
+    #[test]
+    fn contact_sheet_export() {
+        let expected = r"P2
+9 4
+2
+2 2 2 2 2 2 2 2 2
+2 2 0 0 2 0 1 0 2
+2 0 0 1 2 1 0 1 2
+2 2 2 2 2 2 2 2 2
+";
+
+        assert_eq!(map().contact_sheet(&[0, 1], 2), expected);
+    }
+
+    #[test]
+    fn stats_csv_export() {
+        let csv = map().stats_csv(0..2);
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines[0], "tick,safety_factor,variance_x,variance_y");
+        assert_eq!(lines[1], "0,0,0.889,0.222");
+        assert_eq!(lines.len(), 3);
+    }
+}
@@ -2,6 +2,9 @@ use aoc_runner_derive::aoc;
 
 use crate::utils::Grid;
 
+mod animation;
+mod planner;
+
 type Point = (i32, i32);
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -24,6 +27,17 @@ impl From<char> for Action {
     }
 }
 
+impl From<Action> for char {
+    fn from(value: Action) -> Self {
+        match value {
+            Action::Up => '^',
+            Action::Right => '>',
+            Action::Down => 'v',
+            Action::Left => '<',
+        }
+    }
+}
+
 impl Action {
     fn delta(&self) -> Point {
         match self {
@@ -40,6 +54,8 @@ enum Cell {
     Empty,
     Wall,
     Box,
+    BoxLeft,
+    BoxRight,
 }
 
 impl From<char> for Cell {
@@ -47,6 +63,8 @@ impl From<char> for Cell {
         match value {
             '#' => Self::Wall,
             'O' => Self::Box,
+            '[' => Self::BoxLeft,
+            ']' => Self::BoxRight,
             _ => Self::Empty,
         }
     }
@@ -57,6 +75,8 @@ impl Into<char> for Cell {
         match self {
             Self::Wall => '#',
             Self::Box => 'O',
+            Self::BoxLeft => '[',
+            Self::BoxRight => ']',
             _ => ' ',
         }
     }
@@ -78,9 +98,38 @@ impl From<&str> for Grid<Cell> {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Outcome {
+    Moved,
+    Pushed(usize),
+    Blocked,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Moved => write!(f, "moved"),
+            Self::Pushed(1) => write!(f, "pushed 1 box"),
+            Self::Pushed(n) => write!(f, "pushed {} boxes", n),
+            Self::Blocked => write!(f, "blocked"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    action: Action,
+    robot: Point,
+    pushed: Vec<(Point, Cell)>,
+    outcome: Outcome,
+}
+
+#[derive(Clone)]
 pub struct Warehouse {
     grid: Grid<Cell>,
     robot: Point,
+    history: Vec<Step>,
+    cursor: usize,
 }
 
 impl From<&str> for Warehouse {
@@ -93,7 +142,12 @@ impl From<&str> for Warehouse {
             .unwrap();
         let robot = grid.xy_for(robot_idx).unwrap();
 
-        Self { grid: grid, robot }
+        Self {
+            grid,
+            robot,
+            history: vec![],
+            cursor: 0,
+        }
     }
 }
 
@@ -124,13 +178,13 @@ impl Warehouse {
         }
     }
 
-    pub fn boxes(self) -> Vec<Point> {
+    pub fn boxes(&self) -> Vec<Point> {
         self.grid
             .cells
             .iter()
             .enumerate()
             .filter_map(|(idx, &cell)| {
-                if cell == Cell::Box {
+                if cell == Cell::Box || cell == Cell::BoxLeft {
                     Some(self.grid.xy_for(idx).unwrap())
                 } else {
                     None
@@ -141,38 +195,173 @@ impl Warehouse {
 
     fn run_action(&mut self, action: Action) {
         let dir = action.delta();
-        let xy = (self.robot.0 + dir.0, self.robot.1 + dir.1);
-
-        let new_cell = self.grid.get_xy(xy).unwrap();
-        match new_cell {
-            Cell::Wall => {}
-            Cell::Empty => self.robot = xy,
-            Cell::Box => {
-                self.push_box(xy, dir);
+        let robot = self.robot;
+        let xy = (robot.0 + dir.0, robot.1 + dir.1);
+
+        let (pushed, outcome) = match self.grid.get_xy(xy).unwrap() {
+            Cell::Wall => (vec![], Outcome::Blocked),
+            Cell::Empty => (vec![], Outcome::Moved),
+            Cell::Box | Cell::BoxLeft | Cell::BoxRight => match self.boxes_to_push(xy, dir) {
+                Some(pushed) => {
+                    let count = pushed
+                        .iter()
+                        .filter(|(_, cell)| *cell != Cell::BoxRight)
+                        .count();
+                    (pushed, Outcome::Pushed(count))
+                }
+                None => (vec![], Outcome::Blocked),
+            },
+        };
+
+        let step = Step {
+            action,
+            robot,
+            pushed,
+            outcome,
+        };
+        self.apply(&step);
+
+        // a new action discards any undone steps
+        self.history.truncate(self.cursor);
+        self.history.push(step);
+        self.cursor += 1;
+    }
+
+    fn boxes_to_push(&self, position: Point, dir: Point) -> Option<Vec<(Point, Cell)>> {
+        // gather every box cell that would be pushed; wide boxes pull their
+        // other half along, so a vertical push can fan out into a tree
+        let mut pushed: Vec<(Point, Cell)> = vec![];
+        let mut queue = vec![position];
+
+        while let Some(xy) = queue.pop() {
+            if pushed.iter().any(|&(p, _)| p == xy) {
+                continue;
+            }
+
+            let cell = self.grid.get_xy(xy).unwrap();
+            match cell {
+                Cell::Wall => return None,
+                Cell::Empty => continue,
+                Cell::Box => {}
+                Cell::BoxLeft => queue.push((xy.0 + 1, xy.1)),
+                Cell::BoxRight => queue.push((xy.0 - 1, xy.1)),
             }
+
+            pushed.push((xy, cell));
+            queue.push((xy.0 + dir.0, xy.1 + dir.1));
         }
+
+        Some(pushed)
     }
 
-    fn push_box(&mut self, position: Point, dir: Point) {
-        let mut behind = position;
-        while let Some(Cell::Box) = self.grid.get_xy(behind) {
-            behind = (behind.0 + dir.0, behind.1 + dir.1);
+    fn apply(&mut self, step: &Step) {
+        if step.outcome == Outcome::Blocked {
+            return;
         }
 
-        match self.grid.get_xy(behind).unwrap() {
-            Cell::Empty => {
-                self.robot = position;
-                self.grid.set_xy(position, Cell::Empty).unwrap();
-                self.grid.set_xy(behind, Cell::Box).unwrap();
-            }
-            _ => {} // do nothing
-        };
+        let dir = step.action.delta();
+        for &(xy, _) in step.pushed.iter() {
+            self.grid.set_xy(xy, Cell::Empty).unwrap();
+        }
+        for &(xy, cell) in step.pushed.iter() {
+            self.grid
+                .set_xy((xy.0 + dir.0, xy.1 + dir.1), cell)
+                .unwrap();
+        }
+
+        self.robot = (step.robot.0 + dir.0, step.robot.1 + dir.1);
+    }
+
+    fn revert(&mut self, step: &Step) {
+        let dir = step.action.delta();
+        for &(xy, _) in step.pushed.iter() {
+            self.grid
+                .set_xy((xy.0 + dir.0, xy.1 + dir.1), Cell::Empty)
+                .unwrap();
+        }
+        for &(xy, cell) in step.pushed.iter() {
+            self.grid.set_xy(xy, cell).unwrap();
+        }
+
+        self.robot = step.robot;
     }
 }
 
-fn parse_input(input: &str) -> (Warehouse, Vec<Action>) {
+#[allow(dead_code)]
+impl Warehouse {
+    pub fn undo(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+
+        self.cursor -= 1;
+        let step = self.history[self.cursor].clone();
+        self.revert(&step);
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        if self.cursor == self.history.len() {
+            return false;
+        }
+
+        let step = self.history[self.cursor].clone();
+        self.apply(&step);
+        self.cursor += 1;
+        true
+    }
+
+    pub fn replay_to(&mut self, step: usize) {
+        let step = step.min(self.history.len());
+        while self.cursor > step {
+            self.undo();
+        }
+        while self.cursor < step {
+            self.redo();
+        }
+    }
+
+    pub fn step(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn history(&self) -> &[Step] {
+        &self.history
+    }
+
+    pub fn log(&self) -> String {
+        self.history
+            .iter()
+            .enumerate()
+            .map(|(i, step)| {
+                let action: char = step.action.into();
+                format!("{} {} {}\n", i + 1, action, step.outcome)
+            })
+            .collect()
+    }
+}
+
+fn widen(input: &str) -> String {
+    input
+        .chars()
+        .map(|c| match c {
+            '#' => "##",
+            'O' => "[]",
+            '.' => "..",
+            '@' => "@.",
+            '\n' => "\n",
+            _ => "",
+        })
+        .collect()
+}
+
+fn parse_input(input: &str, wide: bool) -> (Warehouse, Vec<Action>) {
     let sections = input.split("\n\n").collect::<Vec<&str>>();
-    let warehouse = Warehouse::from(sections[0]);
+    let warehouse = if wide {
+        Warehouse::from(widen(sections[0]).as_str())
+    } else {
+        Warehouse::from(sections[0])
+    };
     let actions = sections[1]
         .chars()
         .filter(|x| !x.is_whitespace())
@@ -184,8 +373,19 @@ fn parse_input(input: &str) -> (Warehouse, Vec<Action>) {
 
 #[aoc(day15, part1)]
 fn part1(input: &str) -> u64 {
-    let (mut warehouse, actions) = parse_input(input);
+    let (mut warehouse, actions) = parse_input(input, false);
     warehouse.run(&actions);
+    gps_sum(warehouse)
+}
+
+#[aoc(day15, part2)]
+fn part2(input: &str) -> u64 {
+    let (mut warehouse, actions) = parse_input(input, true);
+    warehouse.run(&actions);
+    gps_sum(warehouse)
+}
+
+fn gps_sum(warehouse: Warehouse) -> u64 {
     warehouse
         .boxes()
         .into_iter()
@@ -224,6 +424,201 @@ v^^>>><<^^<>>^v^<v^vv<>v^<<>^<^v^v><^<<<><<^<v><v<>vv>>v><v^<vv<>v^<<^";
         assert_eq!(part1(INPUT), 10092);
     }
 
+    #[test]
+    fn part2_example() {
+        assert_eq!(part2(INPUT), 9021);
+    }
+
+    #[test]
+    fn part2_small_example() {
+        let input = r"#######
+#...#.#
+#.....#
+#..OO@#
+#..O..#
+#.....#
+#######
+
+<vv<<^^<<^^";
+        let (mut warehouse, actions) = parse_input(input, true);
+        warehouse.run(&actions);
+
+        let expected = r"##############
+##...[].##..##
+##...@.[]...##
+##....[]....##
+##..........##
+##..........##
+##############
+";
+        assert_eq!(warehouse.to_string().replace(' ', "."), expected);
+        assert_eq!(gps_sum(warehouse), 105 + 207 + 306);
+    }
+
+    #[test]
+    fn widen_map() {
+        assert_eq!(widen("#O.@\n#..#"), "##[]..@.\n##....##");
+    }
+
+    #[test]
+    fn robot_pushes_wide_boxes_horizontally() {
+        let input = r"########
+#@[][].#
+########";
+        let mut warehouse = Warehouse::from(input);
+        warehouse.run_action(Action::Right);
+
+        assert_eq!(warehouse.robot, (2, 1));
+        assert_eq!(warehouse.to_string().lines().nth(1), Some("# @[][]#"));
+    }
+
+    #[test]
+    fn robot_pushes_tree_of_wide_boxes() {
+        let input = r"########
+#......#
+#.[][].#
+#..[]..#
+#...@..#
+########";
+        let mut warehouse = Warehouse::from(input);
+        warehouse.run_action(Action::Up);
+
+        assert_eq!(warehouse.robot, (4, 3));
+        let expected = r"########
+#.[][].#
+#..[]..#
+#...@..#
+#......#
+########
+";
+        assert_eq!(warehouse.to_string().replace(' ', "."), expected);
+    }
+
+    #[test]
+    fn robot_push_tree_blocked_by_wall() {
+        let input = r"########
+#....#.#
+#.[][].#
+#..[]..#
+#...@..#
+########";
+        let mut warehouse = Warehouse::from(input);
+        let before = warehouse.to_string();
+        warehouse.run_action(Action::Up);
+
+        assert_eq!(warehouse.robot, (4, 4));
+        assert_eq!(warehouse.to_string(), before);
+    }
+
+    #[test]
+    fn robot_pushes_staggered_wide_boxes() {
+        let input = r"#######
+#.....#
+#.[]..#
+#..[].#
+#..@..#
+#######";
+        let mut warehouse = Warehouse::from(input);
+        warehouse.run_action(Action::Up);
+
+        let expected = r"#######
+#.[]..#
+#..[].#
+#..@..#
+#.....#
+#######
+";
+        assert_eq!(warehouse.to_string().replace(' ', "."), expected);
+    }
+
+    #[test]
+    fn undo_and_redo_push() {
+        let input = r"######
+#@OO.#
+######";
+        let mut warehouse = Warehouse::from(input);
+        let before = warehouse.to_string();
+        warehouse.run_action(Action::Right);
+        let after = warehouse.to_string();
+
+        assert!(warehouse.undo());
+        assert_eq!(warehouse.to_string(), before);
+        assert!(!warehouse.undo());
+
+        assert!(warehouse.redo());
+        assert_eq!(warehouse.to_string(), after);
+        assert!(!warehouse.redo());
+    }
+
+    #[test]
+    fn undo_wide_tree_push() {
+        let input = r"########
+#......#
+#.[][].#
+#..[]..#
+#...@..#
+########";
+        let mut warehouse = Warehouse::from(input);
+        let before = warehouse.to_string();
+        warehouse.run_action(Action::Up);
+        warehouse.undo();
+
+        assert_eq!(warehouse.to_string(), before);
+    }
+
+    #[test]
+    fn new_action_discards_redo() {
+        let input = r"#####
+#@..#
+#####";
+        let mut warehouse = Warehouse::from(input);
+        warehouse.run(&[Action::Right, Action::Right]);
+        warehouse.undo();
+        warehouse.run_action(Action::Left);
+
+        assert_eq!(warehouse.history().len(), 2);
+        assert!(!warehouse.redo());
+        assert_eq!(warehouse.robot, (1, 1));
+    }
+
+    #[test]
+    fn replay_to_any_step() {
+        let (mut warehouse, actions) = parse_input(INPUT, true);
+        let initial = warehouse.to_string();
+        warehouse.run(&actions);
+        let last = warehouse.to_string();
+
+        warehouse.replay_to(0);
+        assert_eq!(warehouse.to_string(), initial);
+
+        warehouse.replay_to(500);
+        let (mut expected, _) = parse_input(INPUT, true);
+        expected.run(&actions[..500]);
+        assert_eq!(warehouse.to_string(), expected.to_string());
+
+        warehouse.replay_to(actions.len());
+        assert_eq!(warehouse.step(), actions.len());
+        assert_eq!(warehouse.to_string(), last);
+    }
+
+    #[test]
+    fn move_log_outcomes() {
+        let input = r"#######
+#@OO..#
+#.[]..#
+#.....#
+#######";
+        let mut warehouse = Warehouse::from(input);
+        warehouse.run(&[Action::Right, Action::Down, Action::Up, Action::Up]);
+
+        let expected = r"1 > pushed 2 boxes
+2 v pushed 1 box
+3 ^ moved
+4 ^ blocked
+";
+        assert_eq!(warehouse.log(), expected);
+    }
+
     #[test]
     fn robot_moves_to_empty() {
         let input = r"####
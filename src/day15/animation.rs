@@ -0,0 +1,163 @@
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
+
+use super::{Action, Warehouse};
+
+const CLEAR: &str = "\x1b[2J";
+const HOME: &str = "\x1b[H";
+const HIGHLIGHT: &str = "\x1b[1;33m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Animation {
+    delay: Duration,
+}
+
+#[allow(dead_code)]
+impl Animation {
+    pub fn new(delay: Duration) -> Self {
+        Self { delay }
+    }
+
+    pub fn play(
+        &self,
+        warehouse: &Warehouse,
+        actions: &[Action],
+        out: &mut impl Write,
+    ) -> std::io::Result<()> {
+        write!(out, "{}", CLEAR)?;
+        for frame in self.frames(warehouse, actions) {
+            write!(out, "{}", frame)?;
+            out.flush()?;
+            thread::sleep(self.delay);
+        }
+
+        Ok(())
+    }
+
+    pub fn asciicast(&self, warehouse: &Warehouse, actions: &[Action]) -> String {
+        let width = warehouse.grid.width().max(40);
+        let height = warehouse.grid.height() + 2;
+        let mut out = format!(
+            "{{\"version\": 2, \"width\": {}, \"height\": {}}}\n",
+            width, height
+        );
+
+        let frames = std::iter::once(CLEAR.to_string()).chain(self.frames(warehouse, actions));
+        for (i, frame) in frames.enumerate() {
+            let time = self.delay.as_secs_f64() * i.saturating_sub(1) as f64;
+            out.push_str(&format!("[{:.3}, \"o\", \"{}\"]\n", time, escape(&frame)));
+        }
+
+        out
+    }
+
+    fn frames<'a>(
+        &self,
+        warehouse: &Warehouse,
+        actions: &'a [Action],
+    ) -> impl Iterator<Item = String> + 'a {
+        let mut warehouse = warehouse.clone();
+        let total = actions.len();
+
+        (0..=total).map(move |step| {
+            if step > 0 {
+                warehouse.run_action(actions[step - 1]);
+            }
+            let action = step.checked_sub(1).map(|i| actions[i]);
+            frame(&warehouse, step, total, action)
+        })
+    }
+}
+
+fn frame(warehouse: &Warehouse, step: usize, total: usize, action: Option<Action>) -> String {
+    let mut out = format!("{}step {}/{}  action: ", HOME, step, total);
+    match action {
+        Some(action) => {
+            let action: char = action.into();
+            out.push_str(&format!("{}{}{}", HIGHLIGHT, action, RESET));
+        }
+        None => out.push('-'),
+    }
+    out.push_str("\x1b[K\r\n");
+
+    let map = warehouse.to_string();
+    for line in map.lines() {
+        let line = line.replace('@', &format!("{}@{}", HIGHLIGHT, RESET));
+        out.push_str(&line);
+        out.push_str("\r\n");
+    }
+
+    out
+}
+
+fn escape(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '"' => "\\\"".to_string(),
+            '\\' => "\\\\".to_string(),
+            '\n' => "\\n".to_string(),
+            '\r' => "\\r".to_string(),
+            c if (c as u32) < 0x20 => format!("\\u{:04x}", c as u32),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warehouse() -> Warehouse {
+        Warehouse::from(
+            r"#####
+#@O.#
+#####",
+        )
+    }
+
+    #[test]
+    fn frame_shows_step_and_action() {
+        let frame = frame(&warehouse(), 1, 2, Some(Action::Right));
+
+        assert_eq!(
+            frame,
+            "\x1b[Hstep 1/2  action: \x1b[1;33m>\x1b[0m\x1b[K\r\n#####\r\n#\x1b[1;33m@\x1b[0mO #\r\n#####\r\n"
+        );
+    }
+
+    #[test]
+    fn play_writes_every_frame() {
+        let mut out: Vec<u8> = vec![];
+        let animation = Animation::new(Duration::ZERO);
+        animation
+            .play(&warehouse(), &[Action::Right, Action::Right], &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with(CLEAR));
+        assert_eq!(out.matches(HOME).count(), 3);
+        assert!(out.contains("step 0/2  action: -"));
+        assert!(out.contains("# \x1b[1;33m@\x1b[0mO#"));
+    }
+
+    #[test]
+    fn asciicast_export() {
+        let animation = Animation::new(Duration::from_millis(250));
+        let cast = animation.asciicast(&warehouse(), &[Action::Right]);
+        let lines: Vec<&str> = cast.lines().collect();
+
+        assert_eq!(lines[0], r#"{"version": 2, "width": 40, "height": 5}"#);
+        assert_eq!(lines[1], r#"[0.000, "o", "\u001b[2J"]"#);
+        assert!(lines[2].starts_with(r#"[0.000, "o", "\u001b[Hstep 0/1  action: -"#));
+        assert!(lines[3].starts_with(r#"[0.250, "o", "\u001b[Hstep 1/1  action: \u001b[1;33m>"#));
+        assert_eq!(lines.len(), 4);
+    }
+
+    #[test]
+    fn escape_json_strings() {
+        assert_eq!(escape("a\"b\\c\r\n\x1b"), "a\\\"b\\\\c\\r\\n\\u001b");
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::{HashSet, VecDeque};
+
+use super::{Action, Cell, Outcome, Point, Warehouse};
+
+const ACTIONS: [Action; 4] = [Action::Up, Action::Right, Action::Down, Action::Left];
+
+#[derive(Debug, Clone, PartialEq)]
+#[allow(dead_code)]
+pub enum Goal {
+    Boxes(Vec<Point>),
+    Robot(Point),
+}
+
+#[allow(dead_code)]
+impl Goal {
+    pub fn is_reached(&self, warehouse: &Warehouse) -> bool {
+        match self {
+            Self::Boxes(targets) => {
+                let boxes = warehouse.boxes();
+                boxes.len() == targets.len() && boxes.iter().all(|b| targets.contains(b))
+            }
+            Self::Robot(target) => warehouse.robot == *target,
+        }
+    }
+}
+
+type State = (Point, Vec<Point>);
+
+#[allow(dead_code)]
+impl Warehouse {
+    pub fn plan(&self, goal: &Goal) -> Option<Vec<Action>> {
+        // breadth-first search, so the first plan found is one of the shortest
+        let mut start = self.clone();
+        start.history.clear();
+        start.cursor = 0;
+
+        let mut nodes: Vec<(Warehouse, Option<(usize, Action)>)> = vec![(start, None)];
+        let mut seen: HashSet<State> = HashSet::from([nodes[0].0.state()]);
+        let mut queue = VecDeque::from([0]);
+
+        while let Some(idx) = queue.pop_front() {
+            if goal.is_reached(&nodes[idx].0) {
+                return Some(path_to(&nodes, idx));
+            }
+
+            for action in ACTIONS {
+                let mut next = nodes[idx].0.clone();
+                next.run_action(action);
+                next.history.clear();
+                next.cursor = 0;
+
+                if next.robot == nodes[idx].0.robot {
+                    continue;
+                }
+                if let Goal::Boxes(targets) = goal {
+                    if next.is_deadlocked(targets) {
+                        continue;
+                    }
+                }
+                if seen.insert(next.state()) {
+                    nodes.push((next, Some((idx, action))));
+                    queue.push_back(nodes.len() - 1);
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn check(&self, actions: &[Action], goal: &Goal) -> bool {
+        let mut warehouse = self.clone();
+        warehouse.run(actions);
+        warehouse.history[warehouse.history.len() - actions.len()..]
+            .iter()
+            .all(|step| step.outcome != Outcome::Blocked)
+            && goal.is_reached(&warehouse)
+    }
+
+    fn state(&self) -> State {
+        (self.robot, self.boxes())
+    }
+
+    fn is_deadlocked(&self, targets: &[Point]) -> bool {
+        // a box stuck in a corner can never be pushed again, so unless it is
+        // already on a target the goal is out of reach
+        let is_wall = |(x, y): Point| self.grid.get_xy((x, y)) == Some(Cell::Wall);
+
+        self.boxes()
+            .into_iter()
+            .filter(|position| !targets.contains(position))
+            .any(|(x, y)| {
+                let right = if self.grid.get_xy((x, y)) == Some(Cell::BoxLeft) {
+                    x + 1
+                } else {
+                    x
+                };
+                // a wide box can still be pushed from under one half when the
+                // other half is against a wall
+                let above = (x..=right).map(|x| is_wall((x, y - 1))).collect::<Vec<_>>();
+                let below = (x..=right).map(|x| is_wall((x, y + 1))).collect::<Vec<_>>();
+                let vertical = above.iter().all(|&w| w)
+                    || below.iter().all(|&w| w)
+                    || (above.contains(&true) && below.contains(&true));
+                let horizontal = is_wall((x - 1, y)) || is_wall((right + 1, y));
+
+                vertical && horizontal
+            })
+    }
+}
+
+fn path_to(nodes: &[(Warehouse, Option<(usize, Action)>)], mut idx: usize) -> Vec<Action> {
+    let mut path = vec![];
+    while let Some((parent, action)) = nodes[idx].1 {
+        path.push(action);
+        idx = parent;
+    }
+
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plan_robot_to_position() {
+        let warehouse = Warehouse::from(
+            r"######
+#@.#.#
+#..O.#
+#....#
+######",
+        );
+        let goal = Goal::Robot((4, 1));
+        let plan = warehouse.plan(&goal).unwrap();
+
+        assert_eq!(plan.len(), 7);
+        assert!(warehouse.check(&plan, &goal));
+    }
+
+    #[test]
+    fn plan_box_onto_target() {
+        let warehouse = Warehouse::from(
+            r"#######
+#.....#
+#.@O..#
+#.....#
+#######",
+        );
+        let goal = Goal::Boxes(vec![(3, 3)]);
+        let plan = warehouse.plan(&goal).unwrap();
+
+        assert_eq!(plan, vec![Action::Up, Action::Right, Action::Down]);
+        assert!(warehouse.check(&plan, &goal));
+    }
+
+    #[test]
+    fn plan_wide_boxes() {
+        let warehouse = Warehouse::from(
+            r"##########
+##......##
+##.@[]..##
+##......##
+##########",
+        );
+        let goal = Goal::Boxes(vec![(6, 2)]);
+        let plan = warehouse.plan(&goal).unwrap();
+
+        assert_eq!(plan, vec![Action::Right, Action::Right]);
+        assert!(warehouse.check(&plan, &goal));
+    }
+
+    #[test]
+    fn plan_impossible_goal() {
+        let warehouse = Warehouse::from(
+            r"######
+#@O..#
+######",
+        );
+
+        assert_eq!(warehouse.plan(&Goal::Boxes(vec![(1, 1)])), None);
+    }
+
+    #[test]
+    fn detect_corner_deadlock() {
+        let warehouse = Warehouse::from(
+            r"#####
+#O..#
+#.@.#
+#####",
+        );
+
+        assert!(warehouse.is_deadlocked(&[(2, 1)]));
+        assert!(!warehouse.is_deadlocked(&[(1, 1)]));
+    }
+
+    #[test]
+    fn detect_wide_corner_deadlock() {
+        let warehouse = Warehouse::from(
+            r"########
+##.#...#
+##[]...#
+##..@..#
+########",
+        );
+
+        // the box can still be pushed down from above its left half, so it is
+        // only stuck when the wall covers both halves
+        assert!(!warehouse.is_deadlocked(&[]));
+
+        let warehouse = Warehouse::from(
+            r"########
+##[]...#
+##..@..#
+########",
+        );
+        assert!(warehouse.is_deadlocked(&[]));
+    }
+
+    #[test]
+    fn check_rejects_blocked_moves() {
+        let warehouse = Warehouse::from(
+            r"#####
+#@..#
+#####",
+        );
+        let goal = Goal::Robot((2, 1));
+
+        assert!(warehouse.check(&[Action::Right], &goal));
+        assert!(!warehouse.check(&[Action::Up, Action::Right], &goal));
+    }
+}